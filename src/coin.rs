@@ -1,8 +1,12 @@
-use crate::graphics::{clear, cursor, raw::RawTerminal, BORDER, COIN, PLAYER};
-use rand::Rng;
+use crate::graphics::{
+    async_stdin, clear, cursor, event::Key, input::TermRead, raw::RawTerminal, BORDER, COIN,
+    HAZARD, MAGNET, MULTIPLIER, PLAYER, SPEED_BOOST, TIME_BONUS,
+};
+use rand::{rngs::ThreadRng, Rng};
 use std::{
-    io::{Read, StdoutLock, Write},
-    time,
+    cmp::Ordering,
+    io::{self, StdoutLock, Write},
+    thread, time,
 };
 
 const MANUAL_POS: u16 = 23;
@@ -10,14 +14,283 @@ const PLAY_TIME: u64 = 60;
 const COIN_TIME: u64 = 2;
 const MAIN_POINT: u16 = 2;
 const SIZE: u16 = 20;
+/// The arena never shrinks below this size.
+const MIN_SIZE: u16 = 10;
+/// Seconds the "level cleared" banner stays up.
+const LEVEL_PAUSE: u64 = 1;
+/// Milliseconds between two moves of the demo bot.
+const BOT_TICK: u64 = 150;
+/// Milliseconds between two updates of the board while no key is pressed.
+const TICK: u64 = 50;
+/// Points lost for stepping on a hazard.
+const HAZARD_PENALTY: u32 = 1;
+
+/// One in `POWER_UP_CHANCE` coin relocations spawns a power-up.
+const POWER_UP_CHANCE: u32 = 4;
+/// Seconds a power-up stays on the board before vanishing.
+const POWER_UP_TIME: u64 = 5;
+/// Seconds added to the play time by the time bonus.
+const TIME_BONUS_SECS: u64 = 10;
+/// Distance within which the magnet pulls the coin.
+const MAGNET_RADIUS: u16 = 5;
 
+#[derive(Clone, Copy)]
 struct Position {
     x: u16,
     y: u16,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum PowerUpKind {
+    Magnet,
+    TimeBonus,
+    SpeedBoost,
+    Multiplier,
+}
+
+impl PowerUpKind {
+    const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::Magnet,
+        PowerUpKind::TimeBonus,
+        PowerUpKind::SpeedBoost,
+        PowerUpKind::Multiplier,
+    ];
+
+    fn glyph(self) -> &'static str {
+        match self {
+            PowerUpKind::Magnet => MAGNET,
+            PowerUpKind::TimeBonus => TIME_BONUS,
+            PowerUpKind::SpeedBoost => SPEED_BOOST,
+            PowerUpKind::Multiplier => MULTIPLIER,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::Magnet => "Magnet",
+            PowerUpKind::TimeBonus => "Time bonus",
+            PowerUpKind::SpeedBoost => "Speed boost",
+            PowerUpKind::Multiplier => "Score x2",
+        }
+    }
+
+    /// How long the effect lasts once picked up, in seconds.
+    fn duration(self) -> u64 {
+        match self {
+            PowerUpKind::Magnet | PowerUpKind::SpeedBoost => 8,
+            PowerUpKind::TimeBonus => 3,
+            PowerUpKind::Multiplier => 10,
+        }
+    }
+}
+
+struct PowerUp {
+    kind: PowerUpKind,
+    pos: Position,
+    spawned: time::Instant,
+}
+
+/// Power-ups the player has picked up and when each one runs out.
+#[derive(Default)]
+struct Effects {
+    active: Vec<(PowerUpKind, time::Instant)>,
+}
+
+impl Effects {
+    fn activate(&mut self, kind: PowerUpKind) {
+        let until = time::Instant::now() + time::Duration::from_secs(kind.duration());
+        self.active.retain(|(k, _)| *k != kind);
+        self.active.push((kind, until));
+    }
+
+    fn expire(&mut self) {
+        let now = time::Instant::now();
+        self.active.retain(|(_, until)| *until > now);
+    }
+
+    fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.active.iter().any(|(k, _)| *k == kind)
+    }
+}
+
+/// A single stage of the run: reach `target` points before the time runs out.
+struct Level {
+    /// Points needed to clear the level.
+    target: u32,
+    /// Seconds available to reach the target.
+    time: u64,
+    /// Milliseconds before an untouched coin jumps elsewhere.
+    coin_time: u64,
+    /// The arena spans `MAIN_POINT..=size` on both axes.
+    size: u16,
+    /// Number of hazards scattered over the arena.
+    hazards: usize,
+}
+
+impl Level {
+    /// Build the `n`th level, starting at 1. Each level asks for more points while the coin
+    /// moves faster, the arena shrinks and more hazards appear, up to half the arena.
+    fn new(n: u16) -> Level {
+        let size = SIZE.saturating_sub(n - 1).max(MIN_SIZE);
+        let cells = usize::from(size - MAIN_POINT).pow(2);
+        Level {
+            target: 5 + 3 * u32::from(n - 1),
+            time: PLAY_TIME / 2,
+            coin_time: (COIN_TIME * 1000)
+                .saturating_sub(150 * u64::from(n - 1))
+                .max(600),
+            size,
+            hazards: usize::from(n - 1).min(cells / 2),
+        }
+    }
+}
+
+/// How a level ended.
+enum LevelResult {
+    /// The target was reached in time.
+    Cleared(u32),
+    /// The time ran out before the target was reached.
+    Failed(u32),
+    /// The player pressed 'q'.
+    Quit(u32),
+}
+
+/// Where the player's moves come from.
+trait Controller {
+    /// Returns the key to apply this tick, or `None` if there is none yet. `size` is the
+    /// size of the arena, as in [`Level::size`].
+    fn next_key(
+        &mut self,
+        player: &Position,
+        coin: &Position,
+        hazards: &[Position],
+        size: u16,
+    ) -> Option<Key>;
+}
+
+/// Moves read from the keyboard, without waiting for them.
+struct Keyboard<I>(I);
+
+impl<I: Iterator<Item = Result<Key, io::Error>>> Controller for Keyboard<I> {
+    fn next_key(&mut self, _: &Position, _: &Position, _: &[Position], _: u16) -> Option<Key> {
+        self.0.next().map(Result::unwrap)
+    }
+}
+
+/// A bot that walks greedily towards the coin, one move per tick.
+struct Bot<I> {
+    /// Non-blocking input, only watched for 'q'.
+    stdin: I,
+}
+
+impl<I: Iterator<Item = Result<Key, io::Error>>> Controller for Bot<I> {
+    fn next_key(
+        &mut self,
+        player: &Position,
+        coin: &Position,
+        hazards: &[Position],
+        size: u16,
+    ) -> Option<Key> {
+        thread::sleep(time::Duration::from_millis(BOT_TICK));
+
+        if let Some(Ok(Key::Char('q'))) = self.stdin.next() {
+            return Some(Key::Char('q'));
+        }
+
+        let horizontal = match player.x.cmp(&coin.x) {
+            Ordering::Less => Some(Key::Right),
+            Ordering::Greater => Some(Key::Left),
+            Ordering::Equal => None,
+        };
+        let vertical = match player.y.cmp(&coin.y) {
+            Ordering::Less => Some(Key::Down),
+            Ordering::Greater => Some(Key::Up),
+            Ordering::Equal => None,
+        };
+        let candidates = if coin.x.abs_diff(player.x) >= coin.y.abs_diff(player.y) {
+            [horizontal, vertical]
+        } else {
+            [vertical, horizontal]
+        };
+
+        // Prefer the longer axis, but step around hazards when the other axis is free.
+        let mut fallback = None;
+        for key in candidates.into_iter().flatten() {
+            let mut next = *player;
+            move_player(&mut next, &key, size);
+            if !is_on(&next, hazards) {
+                return Some(key);
+            }
+            fallback.get_or_insert(key);
+        }
+
+        Some(fallback.unwrap_or(Key::Null))
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
-pub fn coin_game<R: Read>(stdin: &mut R, stdout: &mut RawTerminal<StdoutLock>) {
+pub fn coin_game(stdout: &mut RawTerminal<StdoutLock>) {
+    run(&mut Keyboard(async_stdin().keys()), stdout);
+}
+
+/// Let the bot play the coin game until it runs out of time or 'q' is pressed.
+#[allow(clippy::module_name_repetitions)]
+pub fn coin_demo(stdout: &mut RawTerminal<StdoutLock>) {
+    run(
+        &mut Bot {
+            stdin: async_stdin().keys(),
+        },
+        stdout,
+    );
+}
+
+fn run<C: Controller>(controller: &mut C, stdout: &mut RawTerminal<StdoutLock>) {
+    let mut rng = rand::thread_rng();
+    let mut total = 0;
+    let mut level = 1;
+
+    loop {
+        match play_level(controller, stdout, &mut rng, level, total) {
+            LevelResult::Cleared(score) => {
+                total += score;
+                write!(
+                    stdout,
+                    "{}Level {} cleared!",
+                    cursor::Goto(MANUAL_POS, MAIN_POINT + 1),
+                    level
+                )
+                .unwrap();
+                stdout.flush().unwrap();
+                thread::sleep(time::Duration::from_secs(LEVEL_PAUSE));
+                level += 1;
+            }
+            LevelResult::Failed(score) | LevelResult::Quit(score) => {
+                total += score;
+                break;
+            }
+        }
+    }
+
+    write!(
+        stdout,
+        "{}Reached level {} - Score: {}{}",
+        clear::All,
+        level,
+        total,
+        cursor::Show
+    )
+    .unwrap();
+}
+
+fn play_level<C: Controller>(
+    controller: &mut C,
+    stdout: &mut RawTerminal<StdoutLock>,
+    rng: &mut ThreadRng,
+    level_num: u16,
+    total: u32,
+) -> LevelResult {
+    let level = Level::new(level_num);
+
     // init
     write!(
         stdout,
@@ -33,16 +306,30 @@ pub fn coin_game<R: Read>(stdin: &mut R, stdout: &mut RawTerminal<StdoutLock>) {
         y: MAIN_POINT,
     };
     let mut score = 0;
+    let mut play_time = level.time;
+    let mut effects = Effects::default();
+    let mut power_up: Option<PowerUp> = None;
+
+    let mut hazards = Vec::with_capacity(level.hazards);
+    while hazards.len() < level.hazards {
+        let hazard = random_position(rng, level.size);
+        if (hazard.x, hazard.y) != (player.x, player.y) && !is_on(&hazard, &hazards) {
+            hazards.push(hazard);
+        }
+    }
 
     // coin
-    let mut rng = rand::thread_rng();
-    let mut coin = Position {
-        x: rng.gen_range(MAIN_POINT..SIZE),
-        y: rng.gen_range(MAIN_POINT..SIZE),
-    };
+    let mut coin = random_free_position(rng, level.size, &hazards);
 
-    draw_border(stdout);
+    draw_border(stdout, level.size);
+    for hazard in &hazards {
+        draw_hazard(stdout, hazard);
+    }
     draw_coin(stdout, &coin);
+    draw_player(stdout, &player);
+    draw_hud(
+        stdout, level_num, &level, score, total, level.time, &effects,
+    );
 
     let start_time = time::Instant::now();
     let mut coin_time = start_time;
@@ -50,99 +337,181 @@ pub fn coin_game<R: Read>(stdin: &mut R, stdout: &mut RawTerminal<StdoutLock>) {
     stdout.flush().unwrap();
 
     // move character
-    for c in stdin.bytes() {
-        if time_exceeded(start_time, PLAY_TIME) {
-            break;
-        }
-        if time_exceeded(coin_time, COIN_TIME) {
+    while !time_exceeded(start_time, play_time) {
+        let key = controller.next_key(&player, &coin, &hazards, level.size);
+        effects.expire();
+
+        if coin_time.elapsed() > time::Duration::from_millis(level.coin_time) {
             coin_time = time::Instant::now();
             clear_coin(stdout, &coin);
 
-            coin.x = rng.gen_range(MAIN_POINT..SIZE);
-            coin.y = rng.gen_range(MAIN_POINT..SIZE);
+            coin = random_free_position(rng, level.size, &hazards);
             draw_coin(stdout, &coin);
+
+            if power_up.is_none() && rng.gen_ratio(1, POWER_UP_CHANCE) {
+                let kind = PowerUpKind::ALL[rng.gen_range(0..PowerUpKind::ALL.len())];
+                let spawned = PowerUp {
+                    kind,
+                    pos: random_free_position(rng, level.size, &hazards),
+                    spawned: coin_time,
+                };
+                draw_power_up(stdout, &spawned);
+                power_up = Some(spawned);
+            }
         }
 
-        if player.x == coin.x && player.y == coin.y {
-            score += 1;
-            coin_time = time::Instant::now();
+        if let Some(p) = &power_up {
+            if time_exceeded(p.spawned, POWER_UP_TIME) {
+                clear_coin(stdout, &p.pos);
+                power_up = None;
+            }
+        }
 
-            coin.x = rng.gen_range(MAIN_POINT..SIZE);
-            coin.y = rng.gen_range(MAIN_POINT..SIZE);
-            draw_coin(stdout, &coin);
-        } else {
-            clear_player(stdout, &player);
+        let Some(key) = key else {
+            // Nothing to move, but the time and the power-ups still count down.
+            let left = play_time.saturating_sub(start_time.elapsed().as_secs());
+            draw_hud(stdout, level_num, &level, score, total, left, &effects);
+            stdout.flush().unwrap();
+            thread::sleep(time::Duration::from_millis(TICK));
+            continue;
+        };
+
+        clear_player(stdout, &player);
+
+        if key == Key::Char('q') {
+            return LevelResult::Quit(score);
         }
 
-        match c.unwrap() {
-            b'q' => break,
-            b'a' => {
-                if player.x > MAIN_POINT {
-                    player.x -= 1;
-                }
+        let steps = if effects.is_active(PowerUpKind::SpeedBoost) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..steps {
+            move_player(&mut player, &key, level.size);
+
+            if effects.is_active(PowerUpKind::Magnet) {
+                pull_coin(stdout, &mut coin, &player);
             }
-            b'd' => {
-                if player.x < SIZE {
-                    player.x += 1;
-                }
+
+            if player.x == coin.x && player.y == coin.y {
+                score += if effects.is_active(PowerUpKind::Multiplier) {
+                    2
+                } else {
+                    1
+                };
+                coin_time = time::Instant::now();
+
+                coin = random_free_position(rng, level.size, &hazards);
+                draw_coin(stdout, &coin);
             }
-            b'w' => {
-                if player.y > MAIN_POINT {
-                    player.y -= 1;
+
+            if let Some(p) = &power_up {
+                if player.x == p.pos.x && player.y == p.pos.y {
+                    if p.kind == PowerUpKind::TimeBonus {
+                        play_time += TIME_BONUS_SECS;
+                    }
+                    effects.activate(p.kind);
+                    power_up = None;
                 }
             }
-            b's' => {
-                if player.y < SIZE {
-                    player.y += 1;
-                }
+
+            if is_on(&player, &hazards) {
+                score = score.saturating_sub(HAZARD_PENALTY);
             }
-            _ => {}
         }
 
-        if player.x == coin.x && player.y == coin.y {
-            score += 1;
-            coin_time = time::Instant::now();
-
-            coin.x = rng.gen_range(MAIN_POINT..SIZE);
-            coin.y = rng.gen_range(MAIN_POINT..SIZE);
-            draw_coin(stdout, &coin);
+        for hazard in &hazards {
+            draw_hazard(stdout, hazard);
         }
-
         draw_player(stdout, &player);
 
-        write!(
-            stdout,
-            "{}Score: {}",
-            cursor::Goto(MANUAL_POS, MAIN_POINT + 3),
-            score
-        )
-        .unwrap();
+        let left = play_time.saturating_sub(start_time.elapsed().as_secs());
+        draw_hud(stdout, level_num, &level, score, total, left, &effects);
 
         stdout.flush().unwrap();
+
+        if score >= level.target {
+            return LevelResult::Cleared(score);
+        }
     }
 
-    write!(stdout, "{}Score: {}{}", clear::All, score, cursor::Show).unwrap();
+    LevelResult::Failed(score)
+}
+
+/// Move the player one cell in the direction of the key, staying inside the border.
+///
+/// Both the keyboard and the bot go through here.
+fn move_player(player: &mut Position, key: &Key, size: u16) {
+    match key {
+        Key::Char('a') | Key::Left if player.x > MAIN_POINT => player.x -= 1,
+        Key::Char('d') | Key::Right if player.x < size => player.x += 1,
+        Key::Char('w') | Key::Up if player.y > MAIN_POINT => player.y -= 1,
+        Key::Char('s') | Key::Down if player.y < size => player.y += 1,
+        _ => {}
+    }
 }
 
-fn draw_border(stdout: &mut RawTerminal<StdoutLock>) {
-    for i in (MAIN_POINT - 1)..(SIZE + 2) {
+/// Move the coin one cell towards the player if it is within the magnet's reach.
+fn pull_coin(stdout: &mut RawTerminal<StdoutLock>, coin: &mut Position, player: &Position) {
+    if coin.x.abs_diff(player.x) > MAGNET_RADIUS || coin.y.abs_diff(player.y) > MAGNET_RADIUS {
+        return;
+    }
+
+    clear_coin(stdout, coin);
+    coin.x = step_towards(coin.x, player.x);
+    coin.y = step_towards(coin.y, player.y);
+    draw_coin(stdout, coin);
+}
+
+fn step_towards(from: u16, to: u16) -> u16 {
+    match from.cmp(&to) {
+        Ordering::Less => from + 1,
+        Ordering::Greater => from - 1,
+        Ordering::Equal => from,
+    }
+}
+
+fn random_position(rng: &mut ThreadRng, size: u16) -> Position {
+    Position {
+        x: rng.gen_range(MAIN_POINT..size),
+        y: rng.gen_range(MAIN_POINT..size),
+    }
+}
+
+/// Pick a random position that is not covered by a hazard.
+fn random_free_position(rng: &mut ThreadRng, size: u16, hazards: &[Position]) -> Position {
+    loop {
+        let pos = random_position(rng, size);
+        if !is_on(&pos, hazards) {
+            return pos;
+        }
+    }
+}
+
+fn is_on(pos: &Position, cells: &[Position]) -> bool {
+    cells.iter().any(|c| (c.x, c.y) == (pos.x, pos.y))
+}
+
+fn draw_border(stdout: &mut RawTerminal<StdoutLock>, size: u16) {
+    for i in (MAIN_POINT - 1)..(size + 2) {
         write!(
             stdout,
             "{}{}{}{}",
             cursor::Goto(i, MAIN_POINT - 1),
             BORDER,
-            cursor::Goto(i, SIZE + 1),
+            cursor::Goto(i, size + 1),
             BORDER
         )
         .unwrap();
 
-        if i < SIZE {
+        if i < size {
             write!(
                 stdout,
                 "{}{}{}{}",
                 cursor::Goto(MAIN_POINT - 1, i + 1),
                 BORDER,
-                cursor::Goto(SIZE + 1, i + 1),
+                cursor::Goto(size + 1, i + 1),
                 BORDER
             )
             .unwrap();
@@ -150,10 +519,68 @@ fn draw_border(stdout: &mut RawTerminal<StdoutLock>) {
     }
 }
 
+/// Draw the level, the score, the time left and every active power-up with its remaining
+/// seconds.
+fn draw_hud(
+    stdout: &mut RawTerminal<StdoutLock>,
+    level_num: u16,
+    level: &Level,
+    score: u32,
+    total: u32,
+    left: u64,
+    effects: &Effects,
+) {
+    write!(
+        stdout,
+        "{}Level: {}{}{}Score: {}/{} (total {}){}{}Time: {}s{}",
+        cursor::Goto(MANUAL_POS, MAIN_POINT + 2),
+        level_num,
+        clear::UntilNewline,
+        cursor::Goto(MANUAL_POS, MAIN_POINT + 3),
+        score,
+        level.target,
+        total + score,
+        clear::UntilNewline,
+        cursor::Goto(MANUAL_POS, MAIN_POINT + 4),
+        left,
+        clear::UntilNewline
+    )
+    .unwrap();
+
+    let now = time::Instant::now();
+    for (i, kind) in PowerUpKind::ALL.iter().enumerate() {
+        write!(
+            stdout,
+            "{}",
+            cursor::Goto(MANUAL_POS, MAIN_POINT + 6 + i as u16)
+        )
+        .unwrap();
+        if let Some((_, until)) = effects.active.iter().find(|(k, _)| k == kind) {
+            let secs = until.saturating_duration_since(now).as_secs() + 1;
+            write!(stdout, "{} {}: {}s", kind.glyph(), kind.name(), secs).unwrap();
+        }
+        write!(stdout, "{}", clear::UntilNewline).unwrap();
+    }
+}
+
 fn draw_coin(stdout: &mut RawTerminal<StdoutLock>, coin: &Position) {
     write!(stdout, "{}{}", cursor::Goto(coin.x, coin.y), COIN).unwrap();
 }
 
+fn draw_hazard(stdout: &mut RawTerminal<StdoutLock>, hazard: &Position) {
+    write!(stdout, "{}{}", cursor::Goto(hazard.x, hazard.y), HAZARD).unwrap();
+}
+
+fn draw_power_up(stdout: &mut RawTerminal<StdoutLock>, power_up: &PowerUp) {
+    write!(
+        stdout,
+        "{}{}",
+        cursor::Goto(power_up.pos.x, power_up.pos.y),
+        power_up.kind.glyph()
+    )
+    .unwrap();
+}
+
 /// Clear the current coin
 fn clear_coin(stdout: &mut RawTerminal<StdoutLock>, coin: &Position) {
     write!(stdout, "{} ", cursor::Goto(coin.x, coin.y)).unwrap();
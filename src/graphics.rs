@@ -9,13 +9,36 @@ pub const MINE: &str = "*";
 pub const FLAGGED: &str = "F";
 pub const CONCEALED: &str = "▒";
 pub const BORDER: &str = "#";
+pub const PORTAL_BORDER: &str = "░";
 pub const PLAYER: &str = "&";
 pub const COIN: &str = "o";
+pub const GOLDEN_FOOD: &str = "$";
+pub const POISON_FOOD: &str = "%";
+pub const SLOW_FOOD: &str = "~";
+pub const MAGNET: &str = "M";
+pub const TIME_BONUS: &str = "T";
+pub const SPEED_BOOST: &str = "S";
+pub const MULTIPLIER: &str = "X";
+pub const HAZARD: &str = "^";
 pub const VERTICAL_SNAKE_BODY: &str = "║";
 pub const HORIZONTAL_SNAKE_BODY: &str = "═";
-pub const SNAKE_HEAD: &str = "@";
+pub const SNAKE_TOP_LEFT: &str = "╔";
+pub const SNAKE_TOP_RIGHT: &str = "╗";
+pub const SNAKE_BOTTOM_LEFT: &str = "╚";
+pub const SNAKE_BOTTOM_RIGHT: &str = "╝";
+pub const SNAKE_HEAD_UP: &str = "▲";
+pub const SNAKE_HEAD_DOWN: &str = "▼";
+pub const SNAKE_HEAD_LEFT: &str = "◀";
+pub const SNAKE_HEAD_RIGHT: &str = "▶";
+pub const SNAKE_TAIL: &str = "·";
+pub const FOOD_ZONE: &str = "+";
+pub const NO_FOOD: &str = "x";
+pub const OFF_MAP: &str = "▒";
 pub const RACER_TYPE: [char; 10] = ['🐥', '🐶', '🐷', '@', '😃', '💩', '🐌', '🦀', '🌜', '👺'];
 
 pub const GAME_START_PROMPT: &str = "Press 'space' to start";
-pub const RACE_NUMBER_PROMPT: &str = "Enter the number of players. [2 - 9]";
+pub const RACE_NUMBER_PROMPT: &str =
+    "Enter the number of players. [2 - 9], or press 'r' to race the roster";
 pub const GAME_OVER: &str = "Game Over. Press 'q' to exit";
+pub const PAUSED: &str = "PAUSED. Press 'p' to resume or 'q' to exit";
+pub const BOARD_COMPLETE: &str = "Board complete! Press 'r' to play again or 'q' to exit";
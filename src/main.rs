@@ -5,6 +5,7 @@ mod graphics;
 mod mine;
 mod race;
 mod randomizer;
+mod save;
 mod snake;
 
 use graphics::{
@@ -19,7 +20,14 @@ use std::{
 
 use crate::graphics::MAIN_INTRO;
 
-const GAMES: &[&str; 5] = &["coin game", "mine sweeper", "snake", "race", "empty"];
+const GAMES: &[&str; 6] = &[
+    "coin game",
+    "coin demo",
+    "mine sweeper",
+    "snake",
+    "race",
+    "empty",
+];
 
 fn main() {
     let stdin = stdin();
@@ -37,19 +45,15 @@ fn main() {
 
     for c in stdin_lock.borrow_mut().bytes() {
         match c.unwrap() {
-            b'w' => {
-                if selected > 0 {
-                    selected = selected.saturating_sub(1);
-                }
-            }
-            b's' => {
-                if selected < GAMES.len() - 1 {
-                    selected += 1;
-                }
-            }
+            b'w' if selected > 0 => selected -= 1,
+            b's' if selected < GAMES.len() - 1 => selected += 1,
             b' ' => match GAMES[selected] {
                 "coin game" => {
-                    coin::coin_game(&mut stdin_lock, &mut stdout);
+                    coin::coin_game(&mut stdout);
+                    break;
+                }
+                "coin demo" => {
+                    coin::coin_demo(&mut stdout);
                     break;
                 }
                 "mine sweeper" => {
@@ -90,7 +90,7 @@ impl<R: Iterator<Item = Result<Key, std::io::Error>>, W: Write> MineSweeper<R, W
 
     fn read_cell(&mut self, c: usize) {
         if !self.grid[c].observed {
-            self.grid[c].mine = self.rand.read_u8() % 5 == 0;
+            self.grid[c].mine = self.rand.read_u8().is_multiple_of(5);
             self.grid[c].observed = true;
         }
     }
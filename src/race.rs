@@ -4,40 +4,168 @@
     clippy::unused_io_amount
 )]
 
-use crate::graphics::{clear, color, cursor, style, BORDER, RACER_TYPE, RACE_NUMBER_PROMPT};
-use rand::{seq::SliceRandom, Rng};
+mod bet;
+mod events;
+mod finish;
+mod ledger;
+mod roster;
+mod stats;
+
+use crate::graphics::{
+    clear, color, cursor, event::Key, input::TermRead, style, BORDER, RACE_NUMBER_PROMPT,
+};
+use rand::Rng;
 use std::{
-    io::{Read, StdoutLock, Write},
+    collections::VecDeque,
+    io::{self, Read, StdoutLock, Write},
     time::{Duration, Instant},
 };
 use termion::{async_stdin, raw::RawTerminal};
 
+use bet::{Bet, Wallets};
+use ledger::Ledger;
+use roster::Roster;
+use stats::Stats;
+
+/// The most a racer's top speed goes up by with fairness on.
+const MAX_BONUS: usize = 3;
+
+/// The keys the key mashers tap, in the order they are handed out.
+const MASH_KEYS: [char; 4] = ['a', 'l', 'z', 'm'];
+/// How far a tap takes a key masher.
+const TAP_STRIDE: u16 = 4;
+/// How many ticks of taps a key masher's speed is averaged over.
+const TAP_WINDOW: usize = 5;
+/// The fastest a key masher can go, however fast they tap.
+const MAX_MASHED_STRIDE: u16 = 8;
+/// Seconds counted down before a race with key mashers.
+const COUNTDOWN: u64 = 3;
+/// The top speed of a racer with no points in it.
+const BASE_TOP_SPEED: u16 = 4;
+/// The slowest a tired racer's top speed gets.
+const MIN_TOP_SPEED: u16 = 3;
+/// How many ticks each point of stamina keeps a racer from tiring.
+const STAMINA_TICKS: u16 = 4;
+/// How many ticks it takes a tired racer to lose another point of top speed.
+const FATIGUE_TICKS: u16 = 3;
+/// The chance in percent of a lucky stride for each point of luck.
+const LUCK_PERCENT: u16 = 4;
+/// How much further a lucky stride goes.
+const LUCKY_STRIDE: u16 = 2;
+/// How many of the latest track events the ticker shows.
+const TICKER_LENGTH: usize = 4;
+/// How long the race goes on after the winner crosses the line, for the others to finish.
+const CUTOFF: Duration = Duration::from_secs(3);
+
+#[derive(Clone)]
 struct Racer {
     racer_type: char,
     name: String,
     pos: u16,
     speed: u16,
     line_num: u16,
+    stats: Stats,
+    /// How many ticks the racer has been running.
+    ticks: u16,
+    /// How many more ticks a tailwind carries the racer.
+    tailwind: u16,
+    /// How many more ticks the racer naps for.
+    asleep: u16,
+    /// How long after the start the racer crossed the line.
+    finish_time: Option<Duration>,
+    /// How much higher the top speed is, for people who paid for coffee recently when
+    /// fairness is on.
+    bonus: u16,
+    /// The key a key masher taps to run, or `None` for a racer that runs on its own.
+    key: Option<char>,
+    /// How often the key was tapped since the last tick.
+    taps: u16,
+    /// The taps of the last few ticks.
+    recent_taps: VecDeque<u16>,
 }
 
 impl Racer {
-    fn new(name: String, line_num: u16) -> Self {
-        let mut rng = rand::thread_rng();
-        let racer_type = *RACER_TYPE.choose(&mut rng).unwrap();
-
+    fn new(name: String, racer_type: char, line_num: u16) -> Self {
         Self {
             racer_type,
             name,
             pos: 1,
             speed: 1,
             line_num,
+            stats: Stats::roll(&mut rand::thread_rng()),
+            ticks: 0,
+            tailwind: 0,
+            asleep: 0,
+            finish_time: None,
+            bonus: 0,
+            key: None,
+            taps: 0,
+            recent_taps: VecDeque::new(),
         }
     }
 
-    fn run(&mut self) {
-        let mut rng = rand::thread_rng();
-        self.speed = rng.gen_range(1..8);
+    /// The name with the key to tap, if there is one.
+    fn label(&self) -> String {
+        match self.key {
+            Some(key) => format!("{} [{key}]", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// How fast the racer goes next. It picks a speed up to its top speed, which drops
+    /// once it tires, but can only get so much faster than it was going.
+    fn stride(&self, rng: &mut impl Rng) -> u16 {
+        let stats = self.stats;
+        let fatigue = self.ticks.saturating_sub(stats.stamina * STAMINA_TICKS) / FATIGUE_TICKS;
+        let top = (BASE_TOP_SPEED + stats.top_speed + self.bonus)
+            .saturating_sub(fatigue)
+            .max(MIN_TOP_SPEED);
+
+        let speed = rng.gen_range(1..=top).min(self.speed + stats.acceleration);
+        if rng.gen_range(0..100) < stats.luck * LUCK_PERCENT {
+            speed + LUCKY_STRIDE
+        } else {
+            speed
+        }
+    }
+
+    /// How far a key masher gets in a tick, going by how fast they tapped lately.
+    fn mashed_stride(&mut self) -> u16 {
+        self.recent_taps.push_back(self.taps);
+        if self.recent_taps.len() > TAP_WINDOW {
+            self.recent_taps.pop_front();
+        }
+        self.taps = 0;
+
+        let taps: u16 = self.recent_taps.iter().sum();
+        (taps * TAP_STRIDE / self.recent_taps.len() as u16).min(MAX_MASHED_STRIDE + self.bonus)
+    }
+
+    /// How far into the stride that took the racer from `from` to where it is now it
+    /// crossed the line, if it did.
+    fn crossing(&self, from: u16, finish: u16) -> Option<f64> {
+        (from < finish && self.pos >= finish)
+            .then(|| (finish - from) as f64 / (self.pos - from) as f64)
+    }
+
+    fn run(&mut self, rng: &mut impl Rng) {
+        self.ticks += 1;
+        if self.asleep > 0 {
+            self.asleep -= 1;
+            self.speed = 0;
+            self.taps = 0;
+            return;
+        }
+
+        self.speed = match self.key {
+            Some(_) => self.mashed_stride(),
+            None => self.stride(rng),
+        };
         self.pos += self.speed;
+        if self.tailwind > 0 {
+            self.tailwind -= 1;
+            self.pos += events::TAILWIND_STRIDE;
+        }
     }
 }
 
@@ -47,12 +175,36 @@ struct Game<R, W> {
     width: usize,
     /// The play lines.
     lines: u8,
+    /// The columns left of the lanes for the racers' names.
+    margin: u16,
     /// Standard input.
     stdin: R,
     /// Standard output.
     stdout: W,
     /// Racer
     racers: Vec<Racer>,
+    /// Everyone who has raced before.
+    roster: Roster,
+    /// The names and glyphs of the people in the race.
+    entrants: Vec<(String, char)>,
+    /// The coins everyone has to bet with.
+    wallets: Wallets,
+    /// The bets on the race being run.
+    bets: Vec<Bet>,
+    /// What a bet on each racer pays for every coin staked, see `Game::quote_odds`.
+    odds: Vec<f64>,
+    /// Who paid for coffee before.
+    ledger: Ledger,
+    /// Whether people who paid for coffee recently run faster.
+    fair: bool,
+    /// How many of the racers are run by tapping a key rather than on their own.
+    mashers: usize,
+    /// The latest things that happened on the track, newest first.
+    ticker: VecDeque<String>,
+    /// When the race started.
+    started: Instant,
+    /// How long after the start the last tick was.
+    last_tick: Duration,
 }
 
 impl<R: Read, W: Write> Game<R, W> {
@@ -62,16 +214,35 @@ impl<R: Read, W: Write> Game<R, W> {
     fn start(&mut self) {
         write!(self.stdout, "{}", cursor::Hide).unwrap();
         let num_players = self.game_start_prompt();
-        self.reset(num_players);
+        let Some(entrants) = self.name_racers(num_players as usize) else {
+            return;
+        };
+        self.entrants = entrants;
+        if !self.reset() {
+            return;
+        }
 
+        let mut async_stdin = async_stdin().keys();
+        if self.racers.iter().any(|racer| racer.key.is_some()) {
+            self.count_down(&mut async_stdin);
+        }
+        self.started = Instant::now();
+        self.last_tick = Duration::ZERO;
         let mut last_update = Instant::now();
-        let mut async_stdin = async_stdin().bytes();
 
         loop {
-            let input = async_stdin.next();
-
-            if let Some(Ok(b'q')) = input {
-                return;
+            for input in async_stdin.by_ref() {
+                match input {
+                    Ok(Key::Char('q')) => return,
+                    Ok(Key::Char(c)) => {
+                        for racer in &mut self.racers {
+                            if racer.key == Some(c) {
+                                racer.taps += 1;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
             }
 
             if last_update.elapsed() > Duration::from_millis(100) {
@@ -88,10 +259,8 @@ impl<R: Read, W: Write> Game<R, W> {
                 std::thread::sleep(Duration::from_millis(10));
             }
 
-            let (winner, result) = self.check_game_over();
-            if result {
-                if self.game_over(&winner) {
-                    self.reset(num_players);
+            if let Some(ranking) = self.check_game_over() {
+                if self.game_over(&ranking) && self.reset() {
                     continue;
                 }
                 return;
@@ -101,16 +270,80 @@ impl<R: Read, W: Write> Game<R, W> {
 
     /// Reset the game.
     ///
-    /// This will display the starting play area.
-    fn reset(&mut self, num_players: u8) {
-        write!(self.stdout, "{}{}", clear::All, style::Reset).unwrap();
+    /// This will take the bets and then display the starting play area. Returns `false` if
+    /// the player quit while betting.
+    fn reset(&mut self) -> bool {
+        self.lines = self.entrants.len() as u8;
+        self.racers.clear();
+        self.ticker.clear();
+        for (i, (name, glyph)) in self.entrants.iter().enumerate() {
+            let mut racer = Racer::new(name.clone(), *glyph, (i as u16 * 2) + 1);
+            if self.fair {
+                racer.bonus = self.ledger.recent_payments(name).min(MAX_BONUS) as u16;
+            }
+            if i < self.mashers {
+                racer.key = Some(MASH_KEYS[i]);
+            }
+            self.racers.push(racer);
+        }
+        let longest = self.racers.iter().map(|r| r.label().chars().count()).max();
+        self.margin = longest.unwrap_or(0) as u16 + 1;
 
-        self.draw_walls(num_players as u16);
+        if !self.take_bets() {
+            return false;
+        }
+
+        self.draw_track();
+        true
+    }
+
+    /// Draws the lanes with the racers' names and the ticker.
+    fn draw_track(&mut self) {
+        write!(self.stdout, "{}{}", clear::All, style::Reset).unwrap();
+        self.draw_walls(self.lines as u16);
+        for racer in &self.racers {
+            write!(
+                self.stdout,
+                "{}{}",
+                cursor::Goto(1, racer.line_num + 1),
+                racer.label()
+            )
+            .unwrap();
+        }
+        self.draw_ticker();
+    }
 
-        for i in 0..num_players {
-            self.racers
-                .push(Racer::new((i + 1).to_string(), (i as u16 * 2) + 1));
+    /// Count down to the start so the key mashers can get ready, throwing away any taps
+    /// that come too early.
+    fn count_down(&mut self, keys: &mut impl Iterator<Item = io::Result<Key>>) {
+        let row = self.lines as u16 * 2 + 3;
+        for n in (1..=COUNTDOWN).rev() {
+            write!(
+                self.stdout,
+                "{}{}Get ready... {n}",
+                cursor::Goto(1, row),
+                clear::CurrentLine
+            )
+            .unwrap();
+            self.stdout.flush().unwrap();
+            std::thread::sleep(Duration::from_secs(1));
         }
+        while keys.next().is_some() {}
+
+        let keys: Vec<String> = self
+            .racers
+            .iter()
+            .filter_map(|racer| Some(format!("{} taps {}", racer.name, racer.key?)))
+            .collect();
+        write!(
+            self.stdout,
+            "{}{}Go! {}",
+            cursor::Goto(1, row),
+            clear::CurrentLine,
+            keys.join(", ")
+        )
+        .unwrap();
+        self.stdout.flush().unwrap();
     }
 
     fn clear_player(&mut self) {
@@ -118,29 +351,83 @@ impl<R: Read, W: Write> Game<R, W> {
             write!(
                 self.stdout,
                 "{} ",
-                cursor::Goto(racer.pos + 1, racer.line_num + 1)
+                cursor::Goto(self.margin + racer.pos + 1, racer.line_num + 1)
             )
             .unwrap();
         }
     }
 
     fn move_player(&mut self) {
+        let mut rng = rand::thread_rng();
+        let now = self.started.elapsed();
+        let finish = self.finish();
         for racer in &mut self.racers {
-            racer.run();
+            if racer.finish_time.is_some() {
+                continue;
+            }
+            let from = racer.pos;
+            racer.run(&mut rng);
+            if let Some(fraction) = racer.crossing(from, finish) {
+                let stride = now - self.last_tick;
+                racer.finish_time = Some(self.last_tick + stride.mul_f64(fraction));
+                racer.pos = finish;
+            }
+        }
+        self.last_tick = now;
+
+        if let Some(news) = events::strike(&mut self.racers, finish, &mut rng) {
+            self.ticker.push_front(news);
+            self.ticker.truncate(TICKER_LENGTH);
+            self.draw_ticker();
         }
     }
 
+    /// Draws the ticker line below the lanes, cut off at the edge of the track.
+    fn draw_ticker(&mut self) {
+        let news = self
+            .ticker
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("  ·  ");
+        write!(
+            self.stdout,
+            "{}{}{}",
+            cursor::Goto(1, self.lines as u16 * 2 + 2),
+            clear::CurrentLine,
+            news.chars().take(self.width).collect::<String>()
+        )
+        .unwrap();
+    }
+
     #[allow(unused_assignments)]
     fn game_start_prompt(&mut self) -> u8 {
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), RACE_NUMBER_PROMPT).unwrap();
-        self.stdout.flush().unwrap();
         loop {
+            write!(
+                self.stdout,
+                "{}{}{}{}[f] fairness: {}{}[m] key mashers: {}{}[l] who paid for coffee",
+                clear::All,
+                cursor::Goto(1, 1),
+                RACE_NUMBER_PROMPT,
+                cursor::Goto(1, 3),
+                if self.fair { "on" } else { "off" },
+                cursor::Goto(1, 4),
+                self.mashers,
+                cursor::Goto(1, 5)
+            )
+            .unwrap();
+            self.stdout.flush().unwrap();
+
             let mut buf = [0];
             self.stdin.read(&mut buf).unwrap();
 
             match buf[0] {
                 b' ' => return 4,
+                b'r' if self.roster.len() >= 2 => return self.roster.len().min(9) as u8,
                 b'2'..=b'9' => return buf[0] - b'0',
+                b'f' => self.fair = !self.fair,
+                b'm' => self.mashers = (self.mashers + 1) % (MASH_KEYS.len() + 1),
+                b'l' => self.show_ledger(),
                 _ => {}
             }
         }
@@ -158,7 +445,7 @@ impl<R: Read, W: Write> Game<R, W> {
             write!(
                 self.stdout,
                 "{}",
-                cursor::Goto(racer.pos + 1, racer.line_num + 1)
+                cursor::Goto(self.margin + racer.pos + 1, racer.line_num + 1)
             )
             .unwrap();
             self.stdout
@@ -187,35 +474,56 @@ impl<R: Read, W: Write> Game<R, W> {
         write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
     }
 
-    fn check_game_over(&mut self) -> (String, bool) {
-        let mut result = Vec::new();
-        let mut rank = String::new();
-        let mut is_end = false;
+    /// How far the racers have to run.
+    fn finish(&self) -> u16 {
+        self.width as u16 - self.margin
+    }
 
-        for racer in &self.racers {
-            if racer.pos >= self.width as u16 {
-                for racer in &self.racers {
-                    result.push((racer.name.clone(), racer.pos));
-                }
-                is_end = true;
-                break;
-            }
+    /// The racers from first to last once everyone has crossed the line, or the cutoff
+    /// after the winner did has passed. Those who finished are ranked by their time and
+    /// the rest by how far they got.
+    fn check_game_over(&mut self) -> Option<Vec<usize>> {
+        let first = self.racers.iter().filter_map(|r| r.finish_time).min()?;
+        let everyone = self.racers.iter().all(|r| r.finish_time.is_some());
+        if !everyone && self.last_tick < first + CUTOFF {
+            return None;
         }
-        result.sort_by(|a, b| b.1.cmp(&a.1));
 
-        for r in result {
-            rank.push_str(&r.0);
-            rank.push('\n');
-        }
-        (rank, is_end)
+        let mut ranking: Vec<usize> = (0..self.racers.len()).collect();
+        ranking.sort_by_key(|&i| {
+            let racer = &self.racers[i];
+            (
+                racer.finish_time.is_none(),
+                racer.finish_time,
+                std::cmp::Reverse(racer.pos),
+            )
+        });
+        Some(ranking)
     }
 
-    fn game_over(&mut self, winners: &str) -> bool {
+    fn game_over(&mut self, ranking: &[usize]) -> bool {
+        let mut ranking = ranking.to_vec();
+        let photos = self.photo_finish(&mut ranking);
+        let ranking = &ranking[..];
+
+        let mut row = self.lines as u16 * 2 + 3;
+        let payer = self.record_payer(ranking);
+        write!(
+            self.stdout,
+            "{}{payer} pays for coffee!",
+            cursor::Goto(1, row)
+        )
+        .unwrap();
+        row = self.draw_ranking(ranking, &photos, row + 2) + 1;
+        for line in self.settle_bets(ranking[0]) {
+            write!(self.stdout, "{}{}", cursor::Goto(1, row), line).unwrap();
+            row += 1;
+        }
+
         write!(
             self.stdout,
-            "{}Ranking: {}, Press 'q' to exit.",
-            cursor::Goto(20, 15),
-            winners
+            "{}Press 'q' to exit.",
+            cursor::Goto(1, row + 1)
         )
         .unwrap();
         write!(
@@ -237,16 +545,28 @@ impl<R: Read, W: Write> Game<R, W> {
 }
 
 /// Initializes the game.
-fn init<W: Write, R: Read>(mut stdout: W, stdin: R, width: usize) {
+fn init<W: Write, R: Read>(mut stdout: W, stdin: R, width: usize, options: Options) {
     write!(stdout, "{}{}", clear::All, cursor::Goto(1, 1)).unwrap();
     stdout.flush().unwrap();
 
     let mut game = Game {
         width,
         lines: 0,
+        margin: 0,
         stdin,
         stdout,
         racers: Vec::new(),
+        roster: Roster::load(options.roster),
+        entrants: Vec::new(),
+        wallets: Wallets::load(),
+        bets: Vec::new(),
+        odds: Vec::new(),
+        ledger: Ledger::load(),
+        fair: options.fair,
+        mashers: options.mashers,
+        ticker: VecDeque::new(),
+        started: Instant::now(),
+        last_tick: Duration::ZERO,
     };
 
     game.start();
@@ -262,11 +582,46 @@ fn init<W: Write, R: Read>(mut stdout: W, stdin: R, width: usize) {
     game.stdout.flush().unwrap();
 }
 
+/// Settings for a coffee race.
+#[derive(Default)]
+struct Options {
+    /// A roster file to use instead of the saved one.
+    roster: Option<String>,
+    /// Whether fairness starts out on.
+    fair: bool,
+    /// How many key mashers there are to start with.
+    mashers: usize,
+}
+
+impl Options {
+    /// Read `--roster <file>`, `--fair` and `--mash <n>` from the command line.
+    fn from_args() -> Options {
+        let mut options = Options::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--roster" => options.roster = args.next(),
+                "--fair" => options.fair = true,
+                "--mash" => {
+                    options.mashers = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .map_or(0, |n: usize| n.min(MASH_KEYS.len()));
+                }
+                _ => {}
+            }
+        }
+
+        options
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub fn coffee_race<R: Read>(stdin: &mut R, stdout: &mut RawTerminal<StdoutLock>) {
     let width = match termion::terminal_size() {
         Ok((w, _)) => w - 5,
         Err(_) => 200,
     } as usize;
-    init(stdout, stdin, width);
+    init(stdout, stdin, width, Options::from_args());
 }
@@ -0,0 +1,258 @@
+//! Betting on the racers.
+//!
+//! Everyone in the race keeps a wallet of coins between sessions, saved in
+//! `race-wallets.txt` as a name and a balance per line. Before the start each of them may
+//! stake coins on a racer, and a bet on the winner pays the stake times that racer's odds.
+
+use super::{events, Game};
+use crate::{graphics::clear, graphics::cursor, graphics::style, save};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+const FILE: &str = "race-wallets.txt";
+
+/// What a new wallet, or one that has run dry, starts with.
+pub const STARTING_COINS: u32 = 100;
+
+/// How much a stake goes up or down by with a key press.
+const STAKE_STEP: u32 = 10;
+
+/// How many races are run behind the scenes to work out the odds.
+const SIMULATED_RACES: u32 = 2000;
+
+/// The lowest chance of winning the odds are worked out from, so they stay finite.
+const MIN_CHANCE: f64 = 0.01;
+
+/// The coins of everyone who has raced, by name.
+pub struct Wallets(BTreeMap<String, u32>);
+
+impl Wallets {
+    /// Load the saved wallets, skipping lines that cannot be read.
+    pub fn load() -> Wallets {
+        let src = save::read(FILE).unwrap_or_default();
+        Wallets(
+            src.lines()
+                .filter_map(|line| {
+                    let (name, coins) = line.rsplit_once('\t')?;
+                    Some((name.to_string(), coins.parse().ok()?))
+                })
+                .collect(),
+        )
+    }
+
+    fn save(&self) {
+        let text: String = self
+            .0
+            .iter()
+            .map(|(name, coins)| format!("{name}\t{coins}\n"))
+            .collect();
+        save::write_best_effort(FILE, &text);
+    }
+
+    pub fn coins(&self, name: &str) -> u32 {
+        self.0.get(name).copied().unwrap_or(STARTING_COINS)
+    }
+
+    fn set(&mut self, name: &str, coins: u32) {
+        self.0.insert(name.to_string(), coins);
+    }
+}
+
+pub struct Bet {
+    /// The name of whoever placed the bet.
+    pub bettor: String,
+    /// The index of the racer the bet is on.
+    pub racer: usize,
+    pub stake: u32,
+}
+
+impl<R: Read, W: Write> Game<R, W> {
+    /// The chance each racer has of winning, worked out by running the race many times
+    /// over, track events and all. The winner is the one that crosses the line earliest in
+    /// its stride, and a tie counts as a share of a win for each of them. Key mashers are
+    /// taken to run like everyone else, since there is no telling how fast they tap.
+    fn win_chances(&self) -> Vec<f64> {
+        let mut rng = rand::thread_rng();
+        let mut wins = vec![0.0; self.racers.len()];
+        let finish = self.finish();
+
+        for _ in 0..SIMULATED_RACES {
+            let mut racers = self.racers.clone();
+            for racer in &mut racers {
+                racer.key = None;
+            }
+            loop {
+                let mut crossings = Vec::new();
+                for racer in &mut racers {
+                    let from = racer.pos;
+                    racer.run(&mut rng);
+                    crossings.push(racer.crossing(from, finish));
+                }
+                events::strike(&mut racers, finish, &mut rng);
+
+                let Some(first) = crossings.iter().flatten().copied().reduce(f64::min) else {
+                    continue;
+                };
+                let leaders = crossings.iter().filter(|&&c| c == Some(first)).count();
+                for (wins, crossing) in wins.iter_mut().zip(&crossings) {
+                    if *crossing == Some(first) {
+                        *wins += 1.0 / leaders as f64;
+                    }
+                }
+                break;
+            }
+        }
+
+        wins.iter().map(|w| w / SIMULATED_RACES as f64).collect()
+    }
+
+    /// What a bet on each racer pays for every coin staked if it wins.
+    fn quote_odds(&self) -> Vec<f64> {
+        self.win_chances()
+            .iter()
+            .map(|chance| (10.0 / chance.max(MIN_CHANCE)).floor() / 10.0)
+            .collect()
+    }
+
+    /// Let everyone in the race bet on a racer in turn, taking the stakes out of their
+    /// wallets. Returns `false` if the player quit instead.
+    pub(super) fn take_bets(&mut self) -> bool {
+        self.bets.clear();
+        self.odds = self.quote_odds();
+
+        for bettor in 0..self.racers.len() {
+            let name = self.racers[bettor].name.clone();
+            let mut note = String::new();
+            if self.wallets.coins(&name) == 0 {
+                self.wallets.set(&name, STARTING_COINS);
+                note = format!("{name} was broke and gets {STARTING_COINS} fresh coins. ");
+            }
+            let coins = self.wallets.coins(&name);
+
+            let mut racer = None;
+            let mut stake = STAKE_STEP.min(coins);
+            loop {
+                self.draw_bets(&name, coins, racer, stake, &note);
+
+                let mut buf = [0];
+                self.stdin.read(&mut buf).unwrap();
+                match buf[0] {
+                    b'q' => return false,
+                    b'n' => break,
+                    b'1'..=b'9' if ((buf[0] - b'1') as usize) < self.racers.len() => {
+                        racer = Some((buf[0] - b'1') as usize);
+                    }
+                    b'+' | b'=' => stake = (stake + STAKE_STEP).min(coins),
+                    b'-' => stake = stake.saturating_sub(STAKE_STEP).max(1),
+                    b' ' | b'\r' => {
+                        if let Some(racer) = racer {
+                            self.wallets.set(&name, coins - stake);
+                            self.bets.push(Bet {
+                                bettor: name,
+                                racer,
+                                stake,
+                            });
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        true
+    }
+
+    /// Draw the betting screen: every racer's odds, the bets placed so far and the prompt
+    /// for the next bettor.
+    fn draw_bets(&mut self, name: &str, coins: u32, racer: Option<usize>, stake: u32, note: &str) {
+        write!(
+            self.stdout,
+            "{}{}{}Place your bets!{}{:<width$} Odds  Stats",
+            clear::All,
+            style::Reset,
+            cursor::Goto(1, 1),
+            cursor::Goto(1, 3),
+            "Racer",
+            width = self.margin as usize + 7
+        )
+        .unwrap();
+
+        for (i, &odds) in self.odds.iter().enumerate() {
+            let backers: Vec<String> = self
+                .bets
+                .iter()
+                .filter(|bet| bet.racer == i)
+                .map(|bet| format!("{} ({})", bet.bettor, bet.stake))
+                .collect();
+            write!(
+                self.stdout,
+                "{}{}{:>3} {} {:<width$}{:>5.1}x  {}  {}{}",
+                cursor::Goto(1, i as u16 + 4),
+                if racer == Some(i) {
+                    style::Invert.to_string()
+                } else {
+                    String::new()
+                },
+                i + 1,
+                self.racers[i].racer_type,
+                self.racers[i].name,
+                odds,
+                self.racers[i].stats.summary(),
+                backers.join(", "),
+                style::Reset,
+                width = self.margin as usize
+            )
+            .unwrap();
+        }
+
+        let row = self.racers.len() as u16 + 5;
+        write!(
+            self.stdout,
+            "{}{note}{name} has {coins} coins. Pick a racer [1-{}] or press 'n' to sit this one out.",
+            cursor::Goto(1, row),
+            self.racers.len()
+        )
+        .unwrap();
+        if racer.is_some() {
+            write!(
+                self.stdout,
+                "{}Stake: {stake}  [+/-] change it, [space] place the bet",
+                cursor::Goto(1, row + 1)
+            )
+            .unwrap();
+        }
+        self.stdout.flush().unwrap();
+    }
+
+    /// Pay out the bets on the winner and save the wallets. Returns a line about how each
+    /// bet went.
+    pub(super) fn settle_bets(&mut self, winner: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for bet in self.bets.drain(..) {
+            let racer = &self.racers[bet.racer];
+            let coins = self.wallets.coins(&bet.bettor);
+            if bet.racer == winner {
+                let payout = (bet.stake as f64 * self.odds[bet.racer]) as u32;
+                self.wallets.set(&bet.bettor, coins + payout);
+                lines.push(format!(
+                    "{} won {payout} on {} {} and has {} coins.",
+                    bet.bettor,
+                    racer.racer_type,
+                    racer.name,
+                    coins + payout
+                ));
+            } else {
+                lines.push(format!(
+                    "{} lost {} on {} {} and has {coins} coins.",
+                    bet.bettor, bet.stake, racer.racer_type, racer.name
+                ));
+            }
+        }
+
+        self.wallets.save();
+        lines
+    }
+}
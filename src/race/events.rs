@@ -0,0 +1,72 @@
+//! Things that happen on the track during a race.
+
+use super::{stats::MAX_STAT, Racer};
+use rand::{distributions::WeightedIndex, prelude::Distribution, seq::SliceRandom, Rng};
+
+/// The chance in percent that something happens on a tick.
+const EVENT_PERCENT: u32 = 6;
+
+/// How many ticks a tailwind lasts, and how much further it carries a racer each tick.
+pub const TAILWIND_TICKS: u16 = 3;
+pub const TAILWIND_STRIDE: u16 = 3;
+
+/// How many ticks a nap lasts.
+pub const NAP_TICKS: u16 = 4;
+
+#[derive(Clone, Copy)]
+enum Event {
+    /// The racer slips and has to build up speed again.
+    Banana,
+    /// The racer is carried along for a few ticks.
+    Tailwind,
+    /// The racer stops for a few ticks.
+    Nap,
+}
+
+impl Event {
+    const ALL: [Event; 3] = [Event::Banana, Event::Tailwind, Event::Nap];
+
+    fn is_good(self) -> bool {
+        matches!(self, Event::Tailwind)
+    }
+}
+
+/// Maybe make something happen to one of the racers still running, picked by luck. Returns
+/// the news for the ticker if something did.
+pub fn strike(racers: &mut [Racer], finish: u16, rng: &mut impl Rng) -> Option<String> {
+    if rng.gen_range(0..100) >= EVENT_PERCENT {
+        return None;
+    }
+    let event = *Event::ALL.choose(rng)?;
+
+    // The lucky are more likely to get the good things and less likely to get the bad.
+    let weights: Vec<u16> = racers
+        .iter()
+        .map(|racer| {
+            if racer.pos >= finish {
+                0
+            } else if event.is_good() {
+                racer.stats.luck
+            } else {
+                MAX_STAT + 1 - racer.stats.luck
+            }
+        })
+        .collect();
+    let racer = &mut racers[WeightedIndex::new(&weights).ok()?.sample(rng)];
+
+    Some(match event {
+        Event::Banana => {
+            racer.speed = 0;
+            racer.recent_taps.clear();
+            format!("🍌 {} slipped on a banana!", racer.name)
+        }
+        Event::Tailwind => {
+            racer.tailwind = TAILWIND_TICKS;
+            format!("💨 {} caught a tailwind!", racer.name)
+        }
+        Event::Nap => {
+            racer.asleep = NAP_TICKS;
+            format!("💤 {} stopped for a nap!", racer.name)
+        }
+    })
+}
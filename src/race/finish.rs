@@ -0,0 +1,160 @@
+//! Crossing the line: finish times, photo finishes and the final ranking.
+//!
+//! A racer crosses the line somewhere in the middle of a tick, so its finish time is worked
+//! out from how far into its last stride the line was. Racers that finish too close to call
+//! by eye go to a photo finish, which shows them as they were the moment the first of them
+//! crossed.
+
+use super::Game;
+use crate::graphics::{clear, cursor, style};
+use rand::seq::SliceRandom;
+use std::{
+    io::{Read, Write},
+    ops::Range,
+    time::Duration,
+};
+
+/// How close finish times have to be for a photo finish.
+const PHOTO_MARGIN: Duration = Duration::from_millis(5);
+
+/// How long the photo finish is shown for.
+const PHOTO_SECONDS: u64 = 3;
+
+/// The column of the finish line in the photo, and how many milliseconds a column of the
+/// photo is.
+const PHOTO_LINE: u16 = 24;
+const PHOTO_MS_PER_COLUMN: f64 = 0.25;
+
+/// A finish time in seconds, like `1.234s`, or `DNF` for a racer that did not finish.
+fn format_time(time: Option<Duration>) -> String {
+    match time {
+        Some(time) => format!("{:.3}s", time.as_secs_f64()),
+        None => "DNF".to_string(),
+    }
+}
+
+impl<R: Read, W: Write> Game<R, W> {
+    /// Find the racers in the ranking that finished too close to call, settling dead heats
+    /// with a coin toss, and show them in a photo finish. Returns the racers whose place was
+    /// decided by it.
+    pub(super) fn photo_finish(&mut self, ranking: &mut [usize]) -> Vec<usize> {
+        let time = |game: &Self, i: usize| game.racers[i].finish_time;
+
+        // Runs of neighbours in the ranking that finished within the margin of each other.
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        let mut start = 0;
+        for k in 1..=ranking.len() {
+            let close = k < ranking.len()
+                && match (time(self, ranking[k - 1]), time(self, ranking[k])) {
+                    (Some(a), Some(b)) => b - a <= PHOTO_MARGIN,
+                    _ => false,
+                };
+            if !close {
+                if k - start > 1 {
+                    runs.push(start..k);
+                }
+                start = k;
+            }
+        }
+        if runs.is_empty() {
+            return Vec::new();
+        }
+
+        // Racers with exactly the same time are in the ranking in lane order, so toss for it.
+        let mut rng = rand::thread_rng();
+        for run in &runs {
+            let mut k = run.start;
+            while k < run.end {
+                let same = ranking[k..run.end]
+                    .iter()
+                    .take_while(|&&i| time(self, i) == time(self, ranking[k]))
+                    .count();
+                ranking[k..k + same].shuffle(&mut rng);
+                k += same;
+            }
+        }
+
+        let groups: Vec<Vec<usize>> = runs.into_iter().map(|run| ranking[run].to_vec()).collect();
+        self.draw_photos(&groups);
+        std::thread::sleep(Duration::from_secs(PHOTO_SECONDS));
+        self.draw_track();
+        self.draw_player();
+
+        groups.concat()
+    }
+
+    /// Draw the photo finish screen: each group of racers lined up behind the line by how
+    /// long after the first of them they crossed it.
+    fn draw_photos(&mut self, groups: &[Vec<usize>]) {
+        write!(
+            self.stdout,
+            "{}{}{}PHOTO FINISH",
+            clear::All,
+            style::Reset,
+            cursor::Goto(1, 1)
+        )
+        .unwrap();
+
+        let mut row = 3;
+        for group in groups {
+            let first = self.racers[group[0]].finish_time.unwrap_or_default();
+            for (k, &i) in group.iter().enumerate() {
+                let racer = &self.racers[i];
+                let behind = racer.finish_time.unwrap_or_default() - first;
+                let columns = (behind.as_secs_f64() * 1000.0 / PHOTO_MS_PER_COLUMN) as u16;
+                let x = PHOTO_LINE.saturating_sub(2 + columns).max(1);
+                write!(
+                    self.stdout,
+                    "{}{}{}│ {}. {} {}",
+                    cursor::Goto(x, row),
+                    racer.racer_type,
+                    cursor::Goto(PHOTO_LINE, row),
+                    k + 1,
+                    racer.name,
+                    if k == 0 {
+                        format_time(racer.finish_time)
+                    } else if behind.is_zero() {
+                        "dead heat, lost the toss".to_string()
+                    } else {
+                        format!("+{:.3}s", behind.as_secs_f64())
+                    }
+                )
+                .unwrap();
+                row += 1;
+            }
+            row += 1;
+        }
+        self.stdout.flush().unwrap();
+    }
+
+    /// Draw the ranking as a table starting at `row`, marking places decided by a photo
+    /// finish. Returns the row below it.
+    pub(super) fn draw_ranking(&mut self, ranking: &[usize], photos: &[usize], row: u16) -> u16 {
+        write!(
+            self.stdout,
+            "{}Place  {:<width$}  {:>8}",
+            cursor::Goto(1, row),
+            "Racer",
+            "Time",
+            width = self.margin as usize + 3
+        )
+        .unwrap();
+
+        for (place, &i) in ranking.iter().enumerate() {
+            let racer = &self.racers[i];
+            write!(
+                self.stdout,
+                "{}{:>5}  {} {:<width$}  {:>8}{}",
+                cursor::Goto(1, row + place as u16 + 1),
+                place + 1,
+                racer.racer_type,
+                racer.name,
+                format_time(racer.finish_time),
+                if photos.contains(&i) { "  photo" } else { "" },
+                width = self.margin as usize
+            )
+            .unwrap();
+        }
+        row + ranking.len() as u16 + 1
+    }
+}
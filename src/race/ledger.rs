@@ -0,0 +1,188 @@
+//! Who paid for coffee.
+//!
+//! Whoever comes last in a race buys the coffee. Every race is recorded in
+//! `race-ledger.txt`, one per line: the date, the payer and everyone who ran, separated by
+//! tabs.
+
+use super::Game;
+use crate::{
+    graphics::{clear, cursor, style},
+    save,
+};
+use std::{
+    io::{Read, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const FILE: &str = "race-ledger.txt";
+
+/// How many of the latest races count as recent for fairness.
+const RECENT: usize = 5;
+
+/// How many races the ledger screen lists.
+const SHOWN: usize = 10;
+
+struct Entry {
+    /// The day of the race, as `YYYY-MM-DD`.
+    date: String,
+    payer: String,
+    participants: Vec<String>,
+}
+
+pub struct Ledger(Vec<Entry>);
+
+impl Ledger {
+    /// Load the ledger, skipping lines that cannot be read.
+    pub fn load() -> Ledger {
+        let src = save::read(FILE).unwrap_or_default();
+        Ledger(
+            src.lines()
+                .filter_map(|line| {
+                    let mut fields = line.split('\t');
+                    Some(Entry {
+                        date: fields.next()?.to_string(),
+                        payer: fields.next()?.to_string(),
+                        participants: fields.map(str::to_string).collect(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn record(&mut self, payer: &str, participants: Vec<String>) {
+        self.0.push(Entry {
+            date: today(),
+            payer: payer.to_string(),
+            participants,
+        });
+
+        let text: String = self
+            .0
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}\t{}\t{}\n",
+                    entry.date,
+                    entry.payer,
+                    entry.participants.join("\t")
+                )
+            })
+            .collect();
+        save::write_best_effort(FILE, &text);
+    }
+
+    /// How many of the latest races someone paid for.
+    pub fn recent_payments(&self, name: &str) -> usize {
+        self.0
+            .iter()
+            .rev()
+            .take(RECENT)
+            .filter(|entry| entry.payer == name)
+            .count()
+    }
+}
+
+/// The date today in UTC, as `YYYY-MM-DD`.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    // Days since 1970-01-01 to a civil date, counting in 400 year eras from 0000-03-01.
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+impl<R: Read, W: Write> Game<R, W> {
+    /// Record the last racer in the ranking as the one paying for coffee. Returns their
+    /// name.
+    pub(super) fn record_payer(&mut self, ranking: &[usize]) -> String {
+        let payer = self.racers[ranking[ranking.len() - 1]].name.clone();
+        let participants = self.racers.iter().map(|r| r.name.clone()).collect();
+        self.ledger.record(&payer, participants);
+        payer
+    }
+
+    /// Show how often everyone paid and the latest races, until a key is pressed.
+    pub(super) fn show_ledger(&mut self) {
+        let mut totals: Vec<(&str, usize, usize)> = Vec::new();
+        for entry in &self.ledger.0 {
+            for name in &entry.participants {
+                let i = match totals.iter().position(|(known, ..)| known == name) {
+                    Some(i) => i,
+                    None => {
+                        totals.push((name, 0, 0));
+                        totals.len() - 1
+                    }
+                };
+                totals[i].2 += 1;
+                if *name == entry.payer {
+                    totals[i].1 += 1;
+                }
+            }
+        }
+        totals.sort_by_key(|&(name, paid, raced)| (std::cmp::Reverse(paid), raced, name));
+
+        write!(
+            self.stdout,
+            "{}{}{}Who paid for coffee{}{:<16} {:>5} {:>5}",
+            clear::All,
+            style::Reset,
+            cursor::Goto(1, 1),
+            cursor::Goto(1, 3),
+            "Name",
+            "Paid",
+            "Raced"
+        )
+        .unwrap();
+        let mut row = 4;
+        for (name, paid, raced) in totals {
+            write!(
+                self.stdout,
+                "{}{name:<16} {paid:>5} {raced:>5}",
+                cursor::Goto(1, row)
+            )
+            .unwrap();
+            row += 1;
+        }
+
+        row += 1;
+        for entry in self.ledger.0.iter().rev().take(SHOWN) {
+            write!(
+                self.stdout,
+                "{}{}  {} paid, racing {}",
+                cursor::Goto(1, row),
+                entry.date,
+                entry.payer,
+                entry.participants.join(", ")
+            )
+            .unwrap();
+            row += 1;
+        }
+        if self.ledger.0.is_empty() {
+            write!(self.stdout, "{}Nobody has raced yet.", cursor::Goto(1, row)).unwrap();
+            row += 1;
+        }
+
+        write!(
+            self.stdout,
+            "{}Press any key to go back.",
+            cursor::Goto(1, row + 1)
+        )
+        .unwrap();
+        self.stdout.flush().unwrap();
+
+        let mut buf = [0];
+        self.stdin.read(&mut buf).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+//! The team roster: who has raced before and the glyph each of them runs as.
+//!
+//! It is kept in `race-roster.txt`, or in the file given with `--roster <file>`, one person
+//! per line: the glyph, a tab and the name. Whoever raced last comes first, so the setup
+//! screen can offer the same line-up again.
+
+use super::{Game, MASH_KEYS};
+use crate::{
+    graphics::{clear, cursor, style, RACER_TYPE},
+    save,
+};
+use rand::seq::SliceRandom;
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+
+const FILE: &str = "race-roster.txt";
+
+/// The longest name that can be typed in.
+const MAX_NAME: usize = 16;
+
+pub struct Roster {
+    /// The roster file given on the command line, or `None` for the saved one.
+    path: Option<String>,
+    /// Everyone on the roster with their glyph, whoever raced last first.
+    people: Vec<(String, char)>,
+}
+
+impl Roster {
+    /// Load the roster, skipping lines that cannot be read.
+    pub fn load(path: Option<String>) -> Roster {
+        let src = match &path {
+            Some(path) => fs::read_to_string(path).ok(),
+            None => save::read(FILE),
+        };
+        let people = src
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (glyph, name) = line.split_once('\t')?;
+                let mut chars = glyph.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(glyph), None) if !name.is_empty() => Some((name.to_string(), glyph)),
+                    _ => None,
+                }
+            })
+            .collect();
+        Roster { path, people }
+    }
+
+    pub fn len(&self) -> usize {
+        self.people.len()
+    }
+
+    /// The glyph someone ran as last time.
+    fn glyph(&self, name: &str) -> Option<char> {
+        self.people
+            .iter()
+            .find(|(known, _)| known == name)
+            .map(|&(_, glyph)| glyph)
+    }
+
+    /// Put the racers at the top of the roster with the glyphs they picked, and save it.
+    fn enter(&mut self, racers: &[(String, char)]) {
+        self.people
+            .retain(|(name, _)| racers.iter().all(|(racer, _)| racer != name));
+        self.people.splice(0..0, racers.iter().cloned());
+
+        let text: String = self
+            .people
+            .iter()
+            .map(|(name, glyph)| format!("{glyph}\t{name}\n"))
+            .collect();
+        match &self.path {
+            Some(path) => save::write_best_effort_at(Path::new(path), &text),
+            None => save::write_best_effort(FILE, &text),
+        }
+    }
+}
+
+/// The glyph after `glyph` that nobody in `taken` runs as, if there is one.
+fn next_glyph(glyph: char, taken: &[(String, char)]) -> char {
+    let start = RACER_TYPE.iter().position(|&g| g == glyph).unwrap_or(0);
+    (1..=RACER_TYPE.len())
+        .map(|k| RACER_TYPE[(start + k) % RACER_TYPE.len()])
+        .find(|g| taken.iter().all(|(_, other)| other != g))
+        .unwrap_or(glyph)
+}
+
+impl<R: Read, W: Write> Game<R, W> {
+    /// Ask for the name of each racer in turn, starting from the people on the roster, and
+    /// let them pick a glyph. Returns the racers' names and glyphs, or `None` if the player
+    /// quit.
+    pub(super) fn name_racers(&mut self, count: usize) -> Option<Vec<(String, char)>> {
+        let mut racers: Vec<(String, char)> = Vec::new();
+        let mut note = String::new();
+
+        while racers.len() < count {
+            let mut name = self
+                .roster
+                .people
+                .iter()
+                .map(|(name, _)| name)
+                .find(|name| racers.iter().all(|(racer, _)| &racer != name))
+                .cloned()
+                .unwrap_or_default();
+            // A glyph picked with tab sticks until the name is changed again.
+            let mut picked = None;
+            let fresh = random_glyph(&racers);
+
+            loop {
+                let glyph = picked
+                    .or_else(|| self.roster.glyph(&name))
+                    .filter(|&g| racers.iter().all(|&(_, other)| other != g))
+                    .unwrap_or(fresh);
+                self.draw_names(&racers, &name, glyph, &note);
+
+                let mut buf = [0];
+                self.stdin.read(&mut buf).unwrap();
+                note.clear();
+                match buf[0] {
+                    // Escape, since 'q' could be part of a name.
+                    27 => return None,
+                    b'\r' | b'\n' => {
+                        let name = match name.trim() {
+                            "" => (racers.len() + 1).to_string(),
+                            name => name.to_string(),
+                        };
+                        if racers.iter().any(|(racer, _)| *racer == name) {
+                            note = format!("There already is a racer called {name}.");
+                            continue;
+                        }
+                        racers.push((name, glyph));
+                        break;
+                    }
+                    b'\t' => picked = Some(next_glyph(glyph, &racers)),
+                    127 | 8 => {
+                        name.pop();
+                        picked = None;
+                    }
+                    c @ b' '..=b'~' if name.len() < MAX_NAME => {
+                        name.push(c as char);
+                        picked = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.roster.enter(&racers);
+        Some(racers)
+    }
+
+    /// The key the racer in a lane taps, if it is a key masher, to show before its name.
+    fn key_hint(&self, lane: usize) -> String {
+        if lane < self.mashers {
+            format!("[{}] ", MASH_KEYS[lane])
+        } else {
+            String::new()
+        }
+    }
+
+    /// Draw the setup screen: the racers named so far, the name being typed and who else
+    /// is on the roster.
+    fn draw_names(&mut self, racers: &[(String, char)], name: &str, glyph: char, note: &str) {
+        write!(
+            self.stdout,
+            "{}{}{}Who is racing? Type a name, [tab] picks another glyph and [enter] moves on.",
+            clear::All,
+            style::Reset,
+            cursor::Goto(1, 1)
+        )
+        .unwrap();
+
+        for (i, (racer, racer_glyph)) in racers.iter().enumerate() {
+            write!(
+                self.stdout,
+                "{}{:>3} {} {}{}",
+                cursor::Goto(1, i as u16 + 3),
+                i + 1,
+                racer_glyph,
+                self.key_hint(i),
+                racer
+            )
+            .unwrap();
+        }
+        let row = racers.len() as u16 + 3;
+        write!(
+            self.stdout,
+            "{}{:>3} {} {}{}{} {}",
+            cursor::Goto(1, row),
+            racers.len() + 1,
+            glyph,
+            self.key_hint(racers.len()),
+            name,
+            style::Invert,
+            style::Reset
+        )
+        .unwrap();
+
+        let others: Vec<String> = self
+            .roster
+            .people
+            .iter()
+            .filter(|(known, _)| known != name && racers.iter().all(|(racer, _)| racer != known))
+            .map(|(known, glyph)| format!("{glyph} {known}"))
+            .collect();
+        if !others.is_empty() {
+            write!(
+                self.stdout,
+                "{}On the roster: {}",
+                cursor::Goto(1, row + 2),
+                others.join(", ")
+            )
+            .unwrap();
+        }
+        write!(
+            self.stdout,
+            "{}{note}{}Press 'esc' to exit.",
+            cursor::Goto(1, row + 3),
+            cursor::Goto(1, row + 5)
+        )
+        .unwrap();
+        self.stdout.flush().unwrap();
+    }
+}
+
+/// A glyph for someone new, picked at random from the ones nobody else runs as.
+fn random_glyph(taken: &[(String, char)]) -> char {
+    let free: Vec<char> = RACER_TYPE
+        .iter()
+        .copied()
+        .filter(|&g| taken.iter().all(|(_, other)| *other != g))
+        .collect();
+    *free
+        .choose(&mut rand::thread_rng())
+        .unwrap_or(&RACER_TYPE[0])
+}
@@ -0,0 +1,58 @@
+//! What each racer is good at.
+//!
+//! Every racer rolls its stats anew for each race, from the same number of points, so no
+//! racer is better than another overall, only different: a fast starter may tire early and
+//! a lucky one dodges the worst of what happens on the track.
+
+use rand::Rng;
+
+/// The lowest and highest a stat can be.
+pub const MIN_STAT: u16 = 1;
+pub const MAX_STAT: u16 = 5;
+
+/// The points handed out on top of the lowest value of each stat.
+const POINTS: u16 = 8;
+
+#[derive(Clone, Copy)]
+pub struct Stats {
+    /// How fast the racer can go.
+    pub top_speed: u16,
+    /// How much faster it can get from one tick to the next.
+    pub acceleration: u16,
+    /// How long it keeps going before it tires.
+    pub stamina: u16,
+    /// How often it gets a lucky stride, and how likely good rather than bad things happen to
+    /// it on the track.
+    pub luck: u16,
+}
+
+impl Stats {
+    /// Hand out the points at random.
+    pub fn roll(rng: &mut impl Rng) -> Stats {
+        let mut stats = [MIN_STAT; 4];
+        let mut points = POINTS;
+        while points > 0 {
+            let stat = rng.gen_range(0..stats.len());
+            if stats[stat] < MAX_STAT {
+                stats[stat] += 1;
+                points -= 1;
+            }
+        }
+
+        let [top_speed, acceleration, stamina, luck] = stats;
+        Stats {
+            top_speed,
+            acceleration,
+            stamina,
+            luck,
+        }
+    }
+
+    /// The stats in a few characters, like `T3 A2 S4 L3`.
+    pub fn summary(self) -> String {
+        format!(
+            "T{} A{} S{} L{}",
+            self.top_speed, self.acceleration, self.stamina, self.luck
+        )
+    }
+}
@@ -28,7 +28,7 @@ impl Randomizer {
     ///
     /// This is used for collecting entropy to the randomizer.
     pub fn write_u8(&mut self, b: u8) {
-        self.state ^= u64::try_from(b).unwrap();
+        self.state ^= u64::from(b);
         self.read_u8();
     }
 }
@@ -0,0 +1,36 @@
+//! Files the games keep between runs, such as records and wallets.
+//!
+//! They live in `.console-play` in the home directory, or in the working directory if there
+//! is no home directory. The games can do without any of them, so failing to save one is
+//! not worth ending a game over and is left at that.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The directory the files are kept in.
+fn dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".console-play")
+}
+
+/// Read a saved file, or `None` if there is none yet.
+pub fn read(name: &str) -> Option<String> {
+    fs::read_to_string(dir().join(name)).ok()
+}
+
+/// Save a file if possible, replacing whatever was saved under its name before.
+pub fn write_best_effort(name: &str, contents: &str) {
+    write_best_effort_at(&dir().join(name), contents);
+}
+
+/// Save a file at a path of its own if possible, such as one given on the command line.
+pub fn write_best_effort_at(path: &Path, contents: &str) {
+    let _ = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, contents));
+}
@@ -4,19 +4,180 @@
     clippy::unused_io_amount
 )]
 
+mod ai;
+mod editor;
+mod food;
+mod level;
+mod net;
+mod replay;
+mod speed;
+
 use crate::{
     graphics::{
-        async_stdin, clear, color, cursor, raw::IntoRawMode, style, BORDER, COIN, GAME_OVER,
-        GAME_START_PROMPT, HORIZONTAL_SNAKE_BODY, SNAKE_HEAD, VERTICAL_SNAKE_BODY,
+        async_stdin, clear, color, cursor,
+        event::{self, Event, Key},
+        raw::IntoRawMode,
+        style, BOARD_COMPLETE, BORDER, GAME_OVER, GAME_START_PROMPT, HORIZONTAL_SNAKE_BODY, PAUSED,
+        PORTAL_BORDER, SNAKE_BOTTOM_LEFT, SNAKE_BOTTOM_RIGHT, SNAKE_HEAD_DOWN, SNAKE_HEAD_LEFT,
+        SNAKE_HEAD_RIGHT, SNAKE_HEAD_UP, SNAKE_TAIL, SNAKE_TOP_LEFT, SNAKE_TOP_RIGHT,
+        VERTICAL_SNAKE_BODY,
     },
     randomizer::Randomizer,
 };
+use ai::{hamiltonian_cycle, Brain, View};
+use editor::Editor;
+use food::{FoodKind, SpawnTable};
+use level::{Level, Tile};
+use net::{Frame, Host, Message, Peer, SnakeState};
+use replay::{Ghost, Run};
+use speed::{Curve, Difficulty, Pace};
 use std::{
     collections::VecDeque,
     io::{stdout, Read, Write},
+    net::TcpStream,
     time::{Duration, Instant},
 };
 
+/// The smallest board the starting snake and the prompts fit on.
+const MIN_WIDTH: usize = 30;
+const MIN_HEIGHT: usize = 15;
+/// The number of body parts a snake starts with.
+const START_LENGTH: usize = 10;
+/// The most snakes a board holds, players and AI together.
+const MAX_SNAKES: usize = 4;
+/// Colours of the snakes, player one first.
+const SNAKE_COLORS: [u8; MAX_SNAKES] = [7, 11, 13, 14];
+/// Shades of each snake's colour from the head to the tail, for drawing with a gradient.
+const SNAKE_GRADIENTS: [[u8; 4]; MAX_SNAKES] = [
+    [255, 250, 245, 240],
+    [226, 220, 178, 136],
+    [207, 170, 133, 96],
+    [51, 44, 37, 30],
+];
+/// How many turns can be buffered ahead of the snake.
+const MAX_QUEUED_TURNS: usize = 3;
+/// How many body parts poison takes off, and the length it never shrinks a snake below.
+const POISON_SHRINK: usize = 3;
+const MIN_LENGTH: usize = 2;
+/// How many random cells to try for the food before looking through the whole board.
+const FOOD_TRIES: usize = 16;
+/// Rows kept free below the board for the HUD.
+const HUD_HEIGHT: usize = 1;
+/// Seconds counted down before the game picks up again after a pause.
+const RESUME_COUNTDOWN: u64 = 3;
+/// The colour the ghost of the best run is drawn in.
+const GHOST_COLOR: u8 = 239;
+
+/// What happens when the snake runs into the edge of the board.
+#[derive(PartialEq, Clone, Copy, Default)]
+enum Walls {
+    /// Hitting a wall ends the game.
+    #[default]
+    Solid,
+    /// The snake leaves through one side and comes back in through the opposite one.
+    Portal,
+}
+
+impl Walls {
+    fn toggle(self) -> Walls {
+        match self {
+            Walls::Solid => Walls::Portal,
+            Walls::Portal => Walls::Solid,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Walls::Solid => "solid",
+            Walls::Portal => "portal",
+        }
+    }
+}
+
+/// Board settings for a snake game.
+#[derive(Default)]
+struct Options {
+    /// Board width, or the terminal width if unset.
+    width: Option<usize>,
+    /// Board height, or the terminal height if unset.
+    height: Option<usize>,
+    /// The starting wall mode, which can still be changed on the start prompt.
+    walls: Walls,
+    /// A level file to play instead of the built-in campaign.
+    level: Option<String>,
+    /// Whether player one's snake starts out on autopilot.
+    autopilot: bool,
+    /// A spawn table for the kinds of food, see `SpawnTable::parse`.
+    food: Option<String>,
+    /// The starting speed settings, which can still be changed on the start prompt.
+    pace: Pace,
+    /// Whether the snakes fade from the head to the tail.
+    gradient: bool,
+    /// The port to host a network match on.
+    host: Option<u16>,
+    /// The address of a network match to join.
+    join: Option<String>,
+    /// Whether to start in light-cycle mode.
+    tron: bool,
+    /// Whether to race the ghost of the best run.
+    ghost: bool,
+    /// A level file to open in the editor instead of playing.
+    edit: Option<String>,
+}
+
+impl Options {
+    /// Read `--width <n>`, `--height <n>`, `--portal`, `--level <file>`, `--autopilot`,
+    /// `--food <table>`, `--speed <curve>`, `--difficulty <preset>`, `--max-speed <n>`,
+    /// `--gradient`, `--host <port>`, `--join <address>`, `--tron`, `--ghost` and
+    /// `--edit <file>` from the command line.
+    fn from_args() -> Options {
+        let mut options = Options::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => options.width = args.next().and_then(|v| v.parse().ok()),
+                "--height" => options.height = args.next().and_then(|v| v.parse().ok()),
+                "--portal" => options.walls = Walls::Portal,
+                "--level" => options.level = args.next(),
+                "--autopilot" => options.autopilot = true,
+                "--food" => options.food = args.next(),
+                "--speed" => {
+                    if let Some(curve) = args.next().as_deref().and_then(Curve::parse) {
+                        options.pace.curve = curve;
+                    }
+                }
+                "--difficulty" => {
+                    if let Some(difficulty) = args.next().as_deref().and_then(Difficulty::parse) {
+                        options.pace.difficulty = difficulty;
+                    }
+                }
+                "--max-speed" => options.pace.max = args.next().and_then(|v| v.parse().ok()),
+                "--gradient" => options.gradient = true,
+                "--host" => options.host = args.next().and_then(|v| v.parse().ok()),
+                "--join" => options.join = args.next(),
+                "--tron" => options.tron = true,
+                "--ghost" => options.ghost = true,
+                "--edit" => options.edit = args.next(),
+                _ => {}
+            }
+        }
+
+        options
+    }
+
+    /// The board size, taking whatever was not set explicitly from the terminal and leaving
+    /// room for the HUD.
+    fn board_size(&self) -> (usize, usize) {
+        let (cols, rows) = termion::terminal_size().unwrap_or((80, 24));
+        (
+            self.width.unwrap_or(cols as usize),
+            self.height
+                .unwrap_or((rows as usize).saturating_sub(HUD_HEIGHT)),
+        )
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 enum Direction {
     Up,
@@ -25,6 +186,37 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    /// The letter a direction is written as in recordings and over the network.
+    fn code(self) -> char {
+        match self {
+            Direction::Up => 'U',
+            Direction::Down => 'D',
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Direction> {
+        match code {
+            "U" => Some(Direction::Up),
+            "D" => Some(Direction::Down),
+            "L" => Some(Direction::Left),
+            "R" => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
 struct BodyPart {
     x: u16,
     y: u16,
@@ -34,6 +226,10 @@ struct BodyPart {
 struct Food {
     x: u16,
     y: u16,
+    kind: FoodKind,
+    /// The move on which the food moves elsewhere if nobody eats it first. It is counted in
+    /// moves rather than time so that replays come out the same.
+    expires: Option<u64>,
 }
 
 impl BodyPart {
@@ -59,6 +255,23 @@ impl BodyPart {
 struct Snake {
     direction: Direction,
     body: VecDeque<BodyPart>,
+    /// Turns read from the keyboard but not applied yet, one per move.
+    turns: VecDeque<Direction>,
+    /// Whether the snake is still in the round.
+    alive: bool,
+    /// What steers the snake if it is not a player's.
+    brain: Option<Brain>,
+}
+
+/// What a move of the snakes led to.
+enum Step {
+    Moved,
+    /// A snake crashed and the game or the round is over.
+    Crashed,
+    /// There is no room left for the food.
+    BoardFull,
+    /// Player one reached the level's target and the next level has started.
+    LevelUp,
 }
 
 /// The game state.
@@ -67,23 +280,126 @@ struct Game<R, W> {
     width: usize,
     /// The play area height.
     height: usize,
+    /// Whether the walls kill or teleport.
+    walls: Walls,
+    /// The levels to play through, in order.
+    campaign: Vec<Level>,
+    /// Index of the current level in `campaign`.
+    level: usize,
+    /// The number of snakes steered by players.
+    players: usize,
+    /// The number of AI snakes following the players' ones.
+    ai_snakes: usize,
+    /// How the AI snakes play.
+    brain: Brain,
+    /// Whether player one's snake steers itself.
+    autopilot: bool,
+    /// Light-cycle mode: there is no food, the snakes leave their whole trail behind them
+    /// and every round goes to the last one still moving.
+    tron: bool,
+    /// Each cell's position along the Hamiltonian cycle the autopilot follows, empty if the
+    /// current level has none.
+    cycle: Vec<u32>,
+    /// The number of rounds in a versus match.
+    best_of: u32,
+    /// Rounds won by each player in the current match. In light-cycle mode the scores count
+    /// the rounds instead, the AI snakes' included.
+    wins: Vec<u32>,
+    /// The current level's map laid out over the whole board.
+    tiles: Vec<Tile>,
+    /// Whether the current level has food zones, which the food then keeps to.
+    food_zones: bool,
+    /// Both ends of every portal on the current level, as indices into `tiles`.
+    portals: Vec<(usize, usize)>,
+    /// How many live snake parts are on each cell of the board.
+    occupancy: Vec<u8>,
     /// Standard input.
     stdin: R,
     /// Standard output.
     stdout: W,
-    /// Snake
-    snake: Snake,
+    /// Snakes, player one first and the AI snakes last
+    snakes: Vec<Snake>,
     /// Snake's Food
     food: Food,
-    /// Speed
+    /// How often each kind of food shows up.
+    food_table: SpawnTable,
+    /// Speed, in moves per second
     speed: u64,
-    /// Game Score
-    score: i32,
+    /// When the current level started, moved forward by the time spent paused.
+    clock: Instant,
+    /// How the speed changes over the game.
+    pace: Pace,
+    /// Whether the snakes fade from the head to the tail.
+    gradient: bool,
+    /// The clients of a network match this game hosts.
+    host: Option<Host>,
+    /// Whether to race the ghost of the best run in games of one player alone.
+    race_ghost: bool,
+    /// The replay of the best run next to the current one.
+    ghost: Option<Box<Ghost>>,
+    /// The best recorded runs, one for each combination of settings, or `None` if they were
+    /// not loaded, like for a test play in the editor. Runs are then not recorded either.
+    runs: Option<Vec<Run>>,
+    /// The seed the randomizer started the current run with.
+    seed: u64,
+    /// The number of moves made since the run started.
+    tick: u64,
+    /// The turns player one took in the current run, along with the move each was taken on.
+    record: Vec<(u64, Direction)>,
+    /// Game Score of each player
+    scores: Vec<i32>,
     /// The randomizer
     rand: Randomizer,
 }
 
 impl<R: Read, W: Write> Game<R, W> {
+    /// A game of one player on a `width` by `height` board, with every other setting left at
+    /// its default.
+    fn new(width: usize, height: usize, campaign: Vec<Level>, stdin: R, stdout: W) -> Self {
+        Game {
+            width,
+            height,
+            walls: Walls::default(),
+            campaign,
+            level: 0,
+            players: 1,
+            ai_snakes: 0,
+            brain: Brain::Greedy,
+            autopilot: false,
+            tron: false,
+            cycle: Vec::new(),
+            best_of: 3,
+            wins: Vec::new(),
+            tiles: Vec::new(),
+            food_zones: false,
+            portals: Vec::new(),
+            occupancy: Vec::new(),
+            stdin,
+            stdout,
+            snakes: Vec::new(),
+            food: Food {
+                x: 0,
+                y: 0,
+                kind: FoodKind::Normal,
+                expires: None,
+            },
+            food_table: SpawnTable::default(),
+            speed: 0,
+            clock: Instant::now(),
+            pace: Pace::default(),
+            gradient: false,
+            host: None,
+            race_ghost: false,
+            ghost: None,
+            runs: None,
+            seed: 0,
+            tick: 0,
+            record: Vec::new(),
+            scores: Vec::new(),
+            rand: Randomizer::new(0),
+        }
+    }
+
     /// Start the game loop.
     ///
     /// This will listen to events and do the appropriate actions.
@@ -91,329 +407,1489 @@ impl<R: Read, W: Write> Game<R, W> {
         write!(self.stdout, "{}", cursor::Hide).unwrap();
         self.game_start_prompt();
         self.reset();
-        let mut before = Instant::now();
+        let mut next_tick = Instant::now();
 
         loop {
-            let interval = 1000 / self.speed;
             let now = Instant::now();
-            let dt = (now.duration_since(before).subsec_millis()) as u64;
-
-            if dt < interval {
-                std::thread::sleep(Duration::from_millis(interval - dt));
+            if now < next_tick {
+                std::thread::sleep(next_tick - now);
                 continue;
             }
 
-            before = now;
+            // Ticks are scheduled from the previous one rather than from whenever the last
+            // update finished, so the speed does not drift. After a stall, such as waiting on
+            // a prompt, the schedule starts over instead of rushing through the missed ticks.
+            next_tick = (next_tick + Duration::from_micros(1_000_000 / self.speed)).max(now);
 
             if !self.update() {
                 return;
             }
 
-            if self.check_game_over() {
-                if self.game_over() {
-                    self.reset();
-                    continue;
+            match self.step() {
+                Step::Moved => {}
+                Step::LevelUp => continue,
+                Step::Crashed => {
+                    if self.versus() && !self.record_round() {
+                        if self.next_round() {
+                            self.load_level();
+                            continue;
+                        }
+                        return;
+                    }
+                    self.keep_best_run();
+                    if self.game_over(GAME_OVER) {
+                        self.reset();
+                        continue;
+                    }
+                    return;
+                }
+                Step::BoardFull => {
+                    self.keep_best_run();
+                    if self.game_over(BOARD_COMPLETE) {
+                        self.reset();
+                        continue;
+                    }
+                    return;
                 }
-                return;
-            }
-
-            if self.check_eating() {
-                self.score += 1;
-                self.speed += 3;
-                self.grow_snake();
-                self.move_food();
             }
 
-            self.clear_snake();
             self.draw_snake();
+            self.draw_ghost();
             self.draw_food();
+            self.draw_hud();
+            self.broadcast(&Message::Frame(self.frame()));
 
             write!(self.stdout, "{}", style::Reset).unwrap();
             self.stdout.flush().unwrap();
         }
     }
 
-    /// Reset the game.
-    ///
-    /// This will display the starting play area.
-    fn reset(&mut self) {
-        write!(self.stdout, "{}{}", clear::All, style::Reset).unwrap();
+    /// Move every snake once and work out what came of it: crashes, food eaten and levels
+    /// finished.
+    fn step(&mut self) -> Step {
+        self.think();
 
-        self.draw_walls();
+        for i in 0..self.snakes.len() {
+            if !self.snakes[i].alive {
+                continue;
+            }
 
-        self.snake = Snake {
-            direction: Direction::Right,
-            body: vec![
-                BodyPart {
-                    x: 10,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 11,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 12,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 13,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 14,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 15,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 16,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 17,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 18,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-                BodyPart {
-                    x: 19,
-                    y: 10,
-                    direction: Direction::Right,
-                },
-            ]
-            .into_iter()
-            .collect(),
-        };
+            while let Some(direction) = self.snakes[i].turns.pop_front() {
+                if self.turn_snake(i, direction) {
+                    break;
+                }
+            }
 
-        self.food = Food {
-            x: self.width as u16 / 2,
-            y: self.height as u16 / 2,
-        };
+            self.move_snake(i);
+        }
+        self.tick += 1;
+
+        if self.check_game_over() {
+            return Step::Crashed;
+        }
+
+        let mut full = false;
+        for i in 0..self.snakes.len() {
+            if !self.tron && self.snakes[i].alive && self.check_eating(i) {
+                self.eat(i);
+                full |= !self.move_food();
+            }
+        }
+        if !self.tron && self.food.expires.is_some_and(|t| self.tick >= t) {
+            self.draw_tile(self.food.x, self.food.y);
+            full |= !self.move_food();
+        }
+
+        if full {
+            return Step::BoardFull;
+        }
 
-        self.score = 0;
-        self.speed = 10;
+        // The autopilot stays on its level, the cycle is only worked out for open boards.
+        let target = self.campaign[self.level].target;
+        if self.players == 1
+            && !self.autopilot
+            && !self.tron
+            && target.is_some_and(|t| self.scores[0] >= t)
+            && self.level + 1 < self.campaign.len()
+        {
+            self.level += 1;
+            self.load_level();
+            return Step::LevelUp;
+        }
+
+        Step::Moved
     }
 
-    /// Update the game.
+    /// Reset the game with a new seed.
     ///
-    /// This will receive and process input. As well as update the game world.
-    /// Returns false if the game is supposed to be closed.
-    fn update(&mut self) -> bool {
-        let mut key_bytes = [0];
-        self.stdin.read(&mut key_bytes).unwrap();
+    /// This will go back to the first level and display its play area.
+    fn reset(&mut self) {
+        let seed = (0..8).fold(0, |seed, _| seed << 8 | u64::from(self.rand.read_u8()));
+        self.start_run(seed);
+    }
+
+    /// Start a run from `seed`, which decides everything random in it.
+    fn start_run(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rand = Randomizer::new(seed);
+        self.tick = 0;
+        self.record.clear();
+        self.level = 0;
+        self.scores = vec![0; self.players + self.ai_snakes];
+        self.wins = vec![0; self.players];
+        self.load_level();
+        self.ghost = self.summon_ghost();
+    }
+
+    /// Lay out the current level on the board, draw it and put the snakes on their spawn
+    /// points.
+    fn load_level(&mut self) {
+        let level = &self.campaign[self.level];
+        let (left, top) = self.level_offset();
+
+        // Whatever the map leaves of the board inside the board's own walls is walled off, so
+        // a level is played on its map alone.
+        self.tiles = vec![Tile::Empty; self.width * self.height];
+        for y in 1..self.height - 1 {
+            for x in 1..self.width - 1 {
+                self.tiles[y * self.width + x] = Tile::Wall;
+            }
+        }
+        for y in 0..level.height {
+            for x in 0..level.width {
+                let tile = level.tile(x as i32, y as i32).unwrap();
+                self.tiles[(top + y) as usize * self.width + (left + x) as usize] = tile;
+            }
+        }
+        self.food_zones = self.tiles.contains(&Tile::FoodZone);
+        self.portals = (0..10)
+            .filter_map(|n| {
+                let mut ends = (0..self.tiles.len()).filter(|&i| self.tiles[i] == Tile::Portal(n));
+                Some((ends.next()?, ends.next()?))
+            })
+            .collect();
+
+        self.cycle = Vec::new();
+        if self.autopilot && !self.tron && level.is_open() {
+            // The cycle runs through the map as if it were a board of its own.
+            let map = hamiltonian_cycle(level.width + 2, level.height + 2);
+            if !map.is_empty() {
+                self.cycle = vec![u32::MAX; self.width * self.height];
+                for y in 0..level.height as usize {
+                    for x in 0..level.width as usize {
+                        self.cycle[(top as usize + y) * self.width + left as usize + x] =
+                            map[(y + 1) * (level.width as usize + 2) + x + 1];
+                    }
+                }
+            }
+        }
 
-        self.rand.write_u8(key_bytes[0]);
+        let mut taken = Vec::new();
+        self.snakes = (0..self.players + self.ai_snakes)
+            .map(|i| {
+                let (body, direction) = level
+                    .place(i, &taken)
+                    .expect("clamp_ai_snakes keeps the snakes within the room on every level");
+                taken.extend_from_slice(&body);
+
+                let body = body
+                    .into_iter()
+                    .map(|(x, y)| {
+                        let (x, y) = (x + left as i32, y + top as i32);
+                        let (x, y) = self.wrap(x as u16, y as u16);
+                        BodyPart { x, y, direction }
+                    })
+                    .collect();
+
+                let brain = if i >= self.players {
+                    Some(self.brain)
+                } else if i == 0 && self.autopilot && !self.tron {
+                    Some(Brain::Autopilot)
+                } else {
+                    None
+                };
+
+                Snake {
+                    direction,
+                    body,
+                    turns: VecDeque::new(),
+                    alive: true,
+                    brain,
+                }
+            })
+            .collect();
 
-        match key_bytes[0] {
-            b'q' => return false,
-            b'w' => self.turn_snake(Direction::Up),
-            b's' => self.turn_snake(Direction::Down),
-            b'a' => self.turn_snake(Direction::Left),
-            b'd' => self.turn_snake(Direction::Right),
-            _ => {}
+        if !self.cycle.is_empty() {
+            self.place_on_cycle();
         }
 
-        self.move_snake();
+        self.occupancy = vec![0; self.width * self.height];
+        for part in self.snakes.iter().flat_map(|snake| &snake.body) {
+            self.occupancy[part.y as usize * self.width + part.x as usize] += 1;
+        }
 
-        true
+        self.speed = self.pace.start(self.level);
+        self.clock = Instant::now();
+        if !self.tron {
+            self.move_food();
+        }
+        self.redraw();
+
+        let board = Message::Board {
+            width: self.width as u16,
+            height: self.height as u16,
+            walls: self.walls,
+            level: self.level,
+            tiles: self.tiles.clone(),
+            name: self.campaign[self.level].name.clone(),
+        };
+        self.broadcast(&board);
+        self.broadcast(&Message::Frame(self.frame()));
     }
 
-    /// Check if the Snake is overlapping a wall or a body part
-    fn check_game_over(&mut self) -> bool {
-        let head = &self.snake.body.back().unwrap();
+    /// Draw the whole screen from scratch.
+    fn redraw(&mut self) {
+        self.redraw_board();
+        for i in 0..self.snakes.len() {
+            if self.snakes[i].alive {
+                self.draw_one_snake(i);
+            }
+        }
+        self.draw_food();
+        self.draw_hud();
+    }
 
-        self.snake
-            .body
-            .iter()
-            .filter(|part| (head.x, head.y) == (part.x, part.y))
-            .count()
-            > 1
-            || head.x == 0
-            || head.y == 0
-            || head.x == self.width as u16
-            || head.y == self.height as u16 - 1
+    /// Move player one's snake onto the start of the Hamiltonian cycle, so that its body is
+    /// in cycle order from the first move on.
+    fn place_on_cycle(&mut self) {
+        let mut cells: Vec<(u32, u16, u16)> = (0..self.height as u16)
+            .flat_map(|y| (0..self.width as u16).map(move |x| (x, y)))
+            .map(|(x, y)| (self.cycle[y as usize * self.width + x as usize], x, y))
+            .filter(|&(i, _, _)| (i as usize) < START_LENGTH)
+            .collect();
+        cells.sort_unstable();
+
+        // The cycle starts off along a whole row or column, which fits the starting snake.
+        let (_, x0, y0) = cells[0];
+        let (_, x1, y1) = cells[1];
+        let direction = if x1 > x0 {
+            Direction::Right
+        } else if y1 > y0 {
+            Direction::Down
+        } else if x1 < x0 {
+            Direction::Left
+        } else {
+            Direction::Up
+        };
+
+        let snake = &mut self.snakes[0];
+        snake.direction = direction;
+        snake.body = cells
+            .into_iter()
+            .map(|(_, x, y)| BodyPart { x, y, direction })
+            .collect();
     }
 
-    /// Grows the Snake's tail
-    fn grow_snake(&mut self) {
-        let x;
-        let y;
-        let direction;
+    /// Clear the screen and draw the walls.
+    fn redraw_board(&mut self) {
+        write!(self.stdout, "{}{}", clear::All, style::Reset).unwrap();
+        self.draw_walls();
+    }
 
-        {
-            let tail = &self.snake.body.front().unwrap();
+    /// Where the top-left corner of the current level's map sits on the board.
+    fn level_offset(&self) -> (u16, u16) {
+        let level = &self.campaign[self.level];
+        (
+            (self.width as u16 - level.width) / 2,
+            (self.height as u16 - level.height) / 2,
+        )
+    }
 
-            x = match tail.direction {
-                Direction::Left => tail.x + 1,
-                Direction::Right => tail.x - 1,
-                _ => tail.x,
-            };
+    /// The index of a board position in `tiles` and `occupancy`.
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width + x as usize
+    }
 
-            y = match tail.direction {
-                Direction::Up => tail.y + 1,
-                Direction::Down => tail.y - 1,
-                _ => tail.y,
-            };
+    /// The tile at a board position.
+    fn tile(&self, x: u16, y: u16) -> Tile {
+        self.tiles[self.index(x, y)]
+    }
 
-            direction = tail.direction;
-        }
+    /// Whether a cell kills a snake moving into it, as a wall of the map or a solid border.
+    fn is_wall(&self, x: u16, y: u16) -> bool {
+        let border = x == 0 || y == 0 || x == self.width as u16 - 1 || y == self.height as u16 - 1;
+        self.tile(x, y) == Tile::Wall || (border && self.walls == Walls::Solid)
+    }
 
-        self.snake.body.push_front(BodyPart { x, y, direction });
+    /// Whether a cell is free for the food: on the board, not a wall and not under a snake.
+    /// With `zones`, only cells in a food zone are.
+    fn is_free(&self, x: u16, y: u16, zones: bool) -> bool {
+        let tile = self.tile(x, y);
+        let allowed = if zones {
+            tile == Tile::FoodZone
+        } else {
+            tile == Tile::Empty || tile == Tile::FoodZone
+        };
+        allowed && self.occupancy[self.index(x, y)] == 0
     }
 
-    /// Checks if the Snake is overlapping the food
-    fn check_eating(&mut self) -> bool {
-        let head = &self.snake.body.back().unwrap();
-        (head.x, head.y) == (self.food.x, self.food.y)
+    /// Where a snake moving into a cell comes out: the other end of a portal, or the cell
+    /// itself.
+    fn through_portal(&self, x: u16, y: u16) -> (u16, u16) {
+        let here = self.index(x, y);
+        let exit = self.portals.iter().find_map(|&(a, b)| match here {
+            _ if here == a => Some(b),
+            _ if here == b => Some(a),
+            _ => None,
+        });
+        match exit {
+            Some(exit) => ((exit % self.width) as u16, (exit / self.width) as u16),
+            None => (x, y),
+        }
     }
 
-    fn clear_snake(&mut self) {
-        for part in &self.snake.body {
-            write!(self.stdout, "{} ", cursor::Goto(part.x + 1, part.y + 1)).unwrap();
+    /// Add or remove a snake part on the occupancy grid.
+    fn occupy(&mut self, x: u16, y: u16, add: bool) {
+        let i = self.index(x, y);
+        if add {
+            self.occupancy[i] += 1;
+        } else {
+            self.occupancy[i] -= 1;
         }
     }
 
-    fn move_snake(&mut self) {
-        {
-            let tail = self.snake.body.pop_front().unwrap();
-            write!(self.stdout, "{} ", cursor::Goto(tail.x + 1, tail.y + 1)).unwrap();
+    /// Receive and process input.
+    ///
+    /// Returns false if the game is supposed to be closed.
+    fn update(&mut self) -> bool {
+        for key in read_keys(&mut self.stdin) {
+            if key == Key::Char('q') {
+                return false;
+            }
+            if key == Key::Char('p') && !self.pause() {
+                return false;
+            }
+            if let Some((i, direction)) = steering(key, self.local_players()) {
+                if self.snakes[i].brain.is_none() {
+                    if i == 0 {
+                        self.record.push((self.tick, direction));
+                    }
+                    self.queue_turn(i, direction);
+                }
+            }
         }
 
-        for part in &mut self.snake.body {
-            part.crawl();
+        self.read_clients();
+        true
+    }
+
+    /// The number of players at this keyboard, as opposed to ones joined over the network.
+    fn local_players(&self) -> usize {
+        if self.host.is_some() {
+            1
+        } else {
+            self.players
         }
+    }
 
-        let (x, y, direction) = {
-            let head = self.snake.body.back().unwrap();
+    /// Let new clients join while on the start prompt, each with a snake of their own.
+    ///
+    /// Returns whether anyone joined.
+    fn accept_clients(&mut self) -> bool {
+        let room = self.room();
+        let Some(host) = &mut self.host else {
+            return false;
+        };
 
-            match self.snake.direction {
-                Direction::Up => (head.x, head.y - 1, Direction::Up),
-                Direction::Down => (head.x, head.y + 1, Direction::Down),
-                Direction::Left => (head.x - 1, head.y, Direction::Left),
-                Direction::Right => (head.x + 1, head.y, Direction::Right),
+        let mut joined = false;
+        while let Some(mut peer) = host.accept() {
+            if self.players + self.ai_snakes >= room {
+                peer.send(&Message::End("The game is full.".to_string()));
+                continue;
+            }
+            if peer.send(&Message::Welcome {
+                player: self.players,
+            }) {
+                host.clients.push((self.players, peer));
+                self.players += 1;
+                joined = true;
             }
+        }
+        joined
+    }
+
+    /// Apply the turns the clients sent, and turn away anyone joining after the start.
+    fn read_clients(&mut self) {
+        let Some(host) = &mut self.host else {
+            return;
         };
 
-        self.snake.body.push_back(BodyPart { x, y, direction });
+        while let Some(mut peer) = host.accept() {
+            peer.send(&Message::End("The match has already started.".to_string()));
+        }
+
+        let mut turns = Vec::new();
+        host.clients.retain_mut(|(player, peer)| {
+            let Some(messages) = peer.receive() else {
+                return false;
+            };
+            for message in messages {
+                match message {
+                    Message::Turn(direction) => turns.push((*player, direction)),
+                    Message::Quit => return false,
+                    _ => {}
+                }
+            }
+            true
+        });
+
+        for (i, direction) in turns {
+            self.queue_turn(i, direction);
+        }
     }
 
-    fn turn_snake(&mut self, direction: Direction) {
-        match (direction, self.snake.direction) {
-            (Direction::Up, Direction::Down)
-            | (Direction::Down, Direction::Up)
-            | (Direction::Left, Direction::Right)
-            | (Direction::Right, Direction::Left) => (),
-            _ => self.snake.direction = direction,
+    /// Send a message to every client, if this game hosts a network match.
+    fn broadcast(&mut self, message: &Message) {
+        if let Some(host) = &mut self.host {
+            host.broadcast(message);
         }
     }
 
-    fn game_start_prompt(&mut self) {
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), GAME_START_PROMPT).unwrap();
-        self.stdout.flush().unwrap();
-        loop {
-            let mut buf = [0];
-            self.stdin.read(&mut buf).unwrap();
-            self.rand.write_u8(buf[0]);
-            if buf[0] == b' ' {
-                return;
-            }
+    /// The state of the board the clients draw.
+    fn frame(&self) -> Frame {
+        Frame {
+            players: self.players,
+            speed: self.speed,
+            elapsed: self.clock.elapsed().as_secs(),
+            food: (!self.tron).then_some((self.food.x, self.food.y, self.food.kind)),
+            snakes: self
+                .snakes
+                .iter()
+                .zip(&self.scores)
+                .map(|(snake, &score)| SnakeState {
+                    alive: snake.alive,
+                    score,
+                    body: snake.body.iter().map(|p| (p.x, p.y, p.direction)).collect(),
+                })
+                .collect(),
         }
     }
 
-    fn game_over(&mut self) -> bool {
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), GAME_OVER).unwrap();
+    /// Play as a client of a network match: send the player's turns to the host and draw
+    /// whatever the host sends back.
+    fn follow(&mut self, peer: &mut Peer) {
+        // The board this terminal has room for. The host's may be no bigger.
+        let (max_width, max_height) = (self.width, self.height);
         write!(
             self.stdout,
-            "{}",
-            cursor::Goto((self.width as u16 / 2) - 2, self.height as u16 / 2 + 1)
+            "{}Waiting for the host.{}",
+            cursor::Goto(1, 1),
+            cursor::Hide
         )
         .unwrap();
-        write!(self.stdout, "SCORE: {}", self.score).unwrap();
-        self.stdout.flush().unwrap();
 
         loop {
-            // Repeatedly read a single byte.
-            let mut buf = [0];
-            self.stdin.read(&mut buf).unwrap();
+            for key in read_keys(&mut self.stdin) {
+                if key == Key::Char('q') {
+                    peer.send(&Message::Quit);
+                    return;
+                }
+                if let Some((0, direction)) = steering(key, 1) {
+                    peer.send(&Message::Turn(direction));
+                }
+            }
 
-            match buf[0] {
-                b'r' => return true,
-                b'q' => return false,
-                _ => {}
+            let Some(messages) = peer.receive() else {
+                write!(
+                    self.stdout,
+                    "{}The host left the game. Press any key to exit.{}",
+                    cursor::Goto(1, 1),
+                    clear::UntilNewline
+                )
+                .unwrap();
+                wait_for_key(&mut self.stdout, &mut self.stdin);
+                return;
+            };
+
+            for message in messages {
+                match message {
+                    Message::Welcome { player } => {
+                        write!(
+                            self.stdout,
+                            "{}You are player {}. Waiting for the host to start.{}",
+                            cursor::Goto(1, 1),
+                            player + 1,
+                            clear::UntilNewline
+                        )
+                        .unwrap();
+                    }
+                    Message::Board {
+                        width,
+                        height,
+                        walls,
+                        level,
+                        tiles,
+                        name,
+                    } => {
+                        if width as usize > max_width || height as usize > max_height {
+                            peer.send(&Message::Quit);
+                            write!(
+                                self.stdout,
+                                "{}{}The host's board is {width}x{height} but this terminal \
+                                 only fits {max_width}x{max_height}.\n\rPress any key to exit.",
+                                clear::All,
+                                cursor::Goto(1, 1)
+                            )
+                            .unwrap();
+                            wait_for_key(&mut self.stdout, &mut self.stdin);
+                            return;
+                        }
+                        self.width = width as usize;
+                        self.height = height as usize;
+                        self.walls = walls;
+                        self.tiles = tiles;
+                        self.campaign = (0..=level).map(|_| Level::blank(&name)).collect();
+                        self.level = level;
+                        self.snakes.clear();
+                    }
+                    Message::Frame(frame) => self.show_frame(frame),
+                    Message::End(message) => {
+                        write!(
+                            self.stdout,
+                            "{}{} Waiting for the host.{}",
+                            cursor::Goto(1, 1),
+                            message,
+                            clear::UntilNewline
+                        )
+                        .unwrap();
+                    }
+                    Message::Turn(_) | Message::Quit => {}
+                }
             }
+
+            self.stdout.flush().unwrap();
+            std::thread::sleep(Duration::from_millis(10));
         }
     }
 
-    fn draw_horizontal_line(&mut self, chr: &str, width: u16) {
-        for _ in 0..width {
-            self.stdout.write(chr.as_bytes()).unwrap();
+    /// Draw a frame the host sent, erasing the snakes and food of the last one first.
+    fn show_frame(&mut self, frame: Frame) {
+        if self.snakes.is_empty() {
+            self.redraw_board();
+        }
+        let drawn: Vec<(u16, u16)> = self
+            .snakes
+            .iter()
+            .filter(|s| s.alive)
+            .flat_map(|s| &s.body)
+            .map(|part| (part.x, part.y))
+            .collect();
+        for (x, y) in drawn {
+            self.draw_tile(x, y);
         }
+        if !self.tron {
+            self.draw_tile(self.food.x, self.food.y);
+        }
+
+        self.players = frame.players;
+        self.speed = frame.speed;
+        self.clock = Instant::now() - Duration::from_secs(frame.elapsed);
+        self.tron = frame.food.is_none();
+        if let Some((x, y, kind)) = frame.food {
+            self.food = Food {
+                x,
+                y,
+                kind,
+                expires: None,
+            };
+        }
+        self.scores = frame.snakes.iter().map(|s| s.score).collect();
+        self.snakes = frame
+            .snakes
+            .into_iter()
+            .map(|s| Snake {
+                direction: s.body.last().unwrap().2,
+                body: s
+                    .body
+                    .into_iter()
+                    .map(|(x, y, direction)| BodyPart { x, y, direction })
+                    .collect(),
+                turns: VecDeque::new(),
+                alive: s.alive,
+                brain: None,
+            })
+            .collect();
+
+        for i in 0..self.snakes.len() {
+            if self.snakes[i].alive {
+                self.draw_one_snake(i);
+            }
+        }
+        self.draw_food();
+        self.draw_hud();
     }
 
-    /// Move the snake's food.
-    fn move_food(&mut self) {
-        loop {
-            let x = (self.rand.read_u8() as u16 % (self.width as u16 - 2)) + 1;
-            let y = (self.rand.read_u8() as u16 % (self.height as u16 - 2)) + 1;
+    /// Freeze the game until the player resumes, then count down before it goes on.
+    ///
+    /// Returns false if the game is supposed to be closed.
+    fn pause(&mut self) -> bool {
+        let paused = Instant::now();
+        self.draw_overlay(PAUSED);
 
-            if self
-                .snake
-                .body
-                .iter()
-                .any(|part| (x, y) == (part.x, part.y))
-            {
+        loop {
+            let mut buf = [0];
+            if self.stdin.read(&mut buf).unwrap() == 0 {
+                std::thread::sleep(Duration::from_millis(50));
                 continue;
             }
+            match buf[0] {
+                b'p' => break,
+                b'q' => return false,
+                _ => {}
+            }
+        }
 
-            self.food.x = x;
-            self.food.y = y;
-            break;
+        for n in (1..=RESUME_COUNTDOWN).rev() {
+            self.redraw();
+            self.draw_overlay(&n.to_string());
+            std::thread::sleep(Duration::from_secs(1));
+        }
+        self.redraw();
+        self.clock += paused.elapsed();
+        if let Some(ghost) = &mut self.ghost {
+            ghost.delay(paused.elapsed());
         }
+        true
     }
 
-    /// Draws the snake's food.
-    fn draw_food(&mut self) {
+    /// Show a message in the middle of the board.
+    fn draw_overlay(&mut self, message: &str) {
+        let width = message.chars().count() as u16 + 2;
         write!(
             self.stdout,
-            "{}",
-            cursor::Goto(self.food.x + 1, self.food.y + 1)
+            "{}{} {} {}",
+            cursor::Goto(
+                (self.width as u16).saturating_sub(width) / 2 + 1,
+                self.height as u16 / 2 + 1
+            ),
+            style::Invert,
+            message,
+            style::Reset
         )
         .unwrap();
-        self.stdout.write(COIN.as_bytes()).unwrap();
+        self.stdout.flush().unwrap();
     }
 
-    /// Draws the snake.
-    fn draw_snake(&mut self) {
-        for part in &self.snake.body {
-            write!(self.stdout, "{}", cursor::Goto(part.x + 1, part.y + 1)).unwrap();
-            match part.direction {
-                Direction::Up | Direction::Down => {
-                    self.stdout.write(VERTICAL_SNAKE_BODY.as_bytes()).unwrap()
-                }
-                Direction::Left | Direction::Right => {
-                    self.stdout.write(HORIZONTAL_SNAKE_BODY.as_bytes()).unwrap()
+    /// Let every AI snake pick its next turn.
+    ///
+    /// All of them decide on the board as it is before anyone moves.
+    fn think(&mut self) {
+        if !self.snakes.iter().any(|s| s.alive && s.brain.is_some()) {
+            return;
+        }
+        let (width, height) = (self.width as u16, self.height as u16);
+        let level = &self.campaign[self.level];
+        let cycle_length = u32::from(level.width) * u32::from(level.height);
+        let mut blocked = vec![false; self.width * self.height];
+        for y in 0..height {
+            for x in 0..width {
+                // The AI snakes do not know their way through portals, so they keep away.
+                let i = self.index(x, y);
+                blocked[i] =
+                    !self.tile(x, y).is_open() || self.is_wall(x, y) || self.occupancy[i] > 0;
+            }
+        }
+        // Tails move out of the way, except for light cycles.
+        for snake in self.snakes.iter().filter(|s| s.alive && !self.tron) {
+            let tail = snake.body.front().unwrap();
+            let i = self.index(tail.x, tail.y);
+            if self.occupancy[i] == 1 {
+                blocked[i] = false;
+            }
+        }
+
+        for i in 0..self.snakes.len() {
+            let snake = &self.snakes[i];
+            let Some(brain) = snake.brain.filter(|_| snake.alive) else {
+                continue;
+            };
+
+            let mut contested = vec![false; blocked.len()];
+            for (_, other) in self
+                .snakes
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| j != i && other.alive)
+            {
+                let head = other.body.back().unwrap();
+                for (x, y) in [
+                    (head.x, head.y - 1),
+                    (head.x, head.y + 1),
+                    (head.x - 1, head.y),
+                    (head.x + 1, head.y),
+                ] {
+                    let (x, y) = self.wrap(x, y);
+                    contested[y as usize * self.width + x as usize] = true;
                 }
+            }
+
+            // Poison is steered around like a wall, except by the autopilot, which has to
+            // stick to its cycle and does not mind being shorter.
+            let food = self.food.y as usize * self.width + self.food.x as usize;
+            let avoid =
+                self.food.kind == FoodKind::Poison && brain != Brain::Autopilot && !self.tron;
+            let was_blocked = blocked[food];
+            blocked[food] |= avoid;
+
+            let view = View {
+                width,
+                height,
+                walls: self.walls,
+                blocked: &blocked,
+                contested: &contested,
+                body: &snake.body,
+                direction: snake.direction,
+                food: (self.food.x, self.food.y),
+                cycle: &self.cycle,
+                cycle_length,
             };
+            let direction = if self.tron {
+                brain.survive(&view)
+            } else {
+                brain.decide(&view)
+            };
+            blocked[food] = was_blocked;
+            self.queue_turn(i, direction);
+        }
+    }
+
+    /// Queue a turn to be applied on a later move.
+    ///
+    /// Repeats of the last queued direction are dropped, as is anything past
+    /// `MAX_QUEUED_TURNS`.
+    fn queue_turn(&mut self, i: usize, direction: Direction) {
+        let snake = &mut self.snakes[i];
+        let last = *snake.turns.back().unwrap_or(&snake.direction);
+        if direction != last && snake.turns.len() < MAX_QUEUED_TURNS {
+            snake.turns.push_back(direction);
         }
+    }
 
-        let head = self.snake.body.back().unwrap();
+    /// Check if any Snake is overlapping a wall or a body part, its own or another snake's.
+    ///
+    /// Snakes that crashed are marked as dead and taken off the occupancy grid, and dead AI
+    /// snakes are taken off the screen too. Two heads meeting kills both snakes. Returns
+    /// whether a player's snake crashed.
+    fn check_game_over(&mut self) -> bool {
+        let crashed: Vec<bool> = self
+            .snakes
+            .iter()
+            .map(|snake| {
+                let head = snake.body.back().unwrap();
+
+                snake.alive
+                    && (self.occupancy[self.index(head.x, head.y)] > 1
+                        || self.is_wall(head.x, head.y))
+            })
+            .collect();
+
+        for (i, &crashed) in crashed.iter().enumerate() {
+            if crashed {
+                self.snakes[i].alive = false;
+                for k in 0..self.snakes[i].body.len() {
+                    let part = &self.snakes[i].body[k];
+                    self.occupy(part.x, part.y, false);
+                }
+                if i >= self.players || self.tron {
+                    self.clear_one_snake(i);
+                }
+            }
+        }
 
-        write!(self.stdout, "{}", cursor::Goto(head.x + 1, head.y + 1)).unwrap();
-        self.stdout.write(SNAKE_HEAD.as_bytes()).unwrap();
+        if self.tron {
+            return self.snakes.iter().filter(|s| s.alive).count() <= 1;
+        }
+        crashed[..self.players].contains(&true)
+    }
+
+    /// Map a position on a portal wall to the matching cell on the opposite side.
+    fn wrap(&self, x: u16, y: u16) -> (u16, u16) {
+        wrap_position(self.walls, self.width as u16, self.height as u16, x, y)
+    }
+
+    /// Grows the Snake's tail
+    fn grow_snake(&mut self, i: usize) {
+        let x;
+        let y;
+        let direction;
+
+        {
+            let tail = &self.snakes[i].body.front().unwrap();
+
+            x = match tail.direction {
+                Direction::Left => tail.x + 1,
+                Direction::Right => tail.x - 1,
+                _ => tail.x,
+            };
+
+            y = match tail.direction {
+                Direction::Up => tail.y + 1,
+                Direction::Down => tail.y - 1,
+                _ => tail.y,
+            };
+
+            direction = tail.direction;
+        }
+
+        let (x, y) = self.wrap(x, y);
+        self.occupy(x, y, true);
+        self.snakes[i].body.push_front(BodyPart { x, y, direction });
+    }
+
+    /// Shrink the snake by taking parts off its tail.
+    fn shrink_snake(&mut self, i: usize) {
+        for _ in 0..POISON_SHRINK {
+            if self.snakes[i].body.len() <= MIN_LENGTH {
+                break;
+            }
+            let tail = self.snakes[i].body.pop_front().unwrap();
+            self.occupy(tail.x, tail.y, false);
+            self.draw_tile(tail.x, tail.y);
+        }
+    }
+
+    /// Apply the food the `i`th snake just ate: score it, grow or shrink the snake and, for a
+    /// player's snake, change the speed.
+    fn eat(&mut self, i: usize) {
+        let kind = self.food.kind;
+        self.scores[i] += kind.points();
+
+        if kind == FoodKind::Poison {
+            self.shrink_snake(i);
+        } else {
+            self.grow_snake(i);
+        }
+
+        if i < self.players {
+            match kind {
+                FoodKind::Normal | FoodKind::Golden => self.speed = self.pace.faster(self.speed),
+                FoodKind::Slow => self.speed = self.pace.slower(self.speed, self.level),
+                FoodKind::Poison => {}
+            }
+        }
+    }
+
+    /// Checks if the Snake is overlapping the food
+    fn check_eating(&mut self, i: usize) -> bool {
+        let head = &self.snakes[i].body.back().unwrap();
+        (head.x, head.y) == (self.food.x, self.food.y)
+    }
+
+    /// Erase a dead snake, leaving the cells other snakes are on alone.
+    fn clear_one_snake(&mut self, i: usize) {
+        let cells: Vec<(u16, u16)> = self.snakes[i]
+            .body
+            .iter()
+            .map(|part| (part.x, part.y))
+            .filter(|&(x, y)| self.occupancy[self.index(x, y)] == 0)
+            .collect();
+        for (x, y) in cells {
+            self.draw_tile(x, y);
+        }
+    }
+
+    fn move_snake(&mut self, i: usize) {
+        // Light cycles keep their whole trail.
+        if !self.tron {
+            let tail = self.snakes[i].body.pop_front().unwrap();
+            self.occupy(tail.x, tail.y, false);
+            self.draw_tile(tail.x, tail.y);
+        }
+
+        for part in &mut self.snakes[i].body {
+            part.crawl();
+        }
+
+        let (x, y, direction) = {
+            let snake = &self.snakes[i];
+            let head = snake.body.back().unwrap();
+
+            match snake.direction {
+                Direction::Up => (head.x, head.y - 1, Direction::Up),
+                Direction::Down => (head.x, head.y + 1, Direction::Down),
+                Direction::Left => (head.x - 1, head.y, Direction::Left),
+                Direction::Right => (head.x + 1, head.y, Direction::Right),
+            }
+        };
+
+        let (x, y) = self.wrap(x, y);
+        let (x, y) = self.through_portal(x, y);
+        self.occupy(x, y, true);
+        self.snakes[i].body.push_back(BodyPart { x, y, direction });
+    }
+
+    /// Turn the snake, unless that would reverse it onto itself.
+    ///
+    /// Returns whether the direction changed.
+    fn turn_snake(&mut self, i: usize, direction: Direction) -> bool {
+        let snake = &mut self.snakes[i];
+        if direction == snake.direction.opposite() {
+            return false;
+        }
+
+        let turned = snake.direction != direction;
+        snake.direction = direction;
+        turned
+    }
+
+    fn game_start_prompt(&mut self) {
+        self.draw_start_prompt();
+        loop {
+            if self.accept_clients() {
+                self.clamp_ai_snakes();
+                self.reset();
+                self.draw_start_prompt();
+            }
+
+            let mut buf = [0];
+            self.stdin.read(&mut buf).unwrap();
+            self.rand.write_u8(buf[0]);
+            match buf[0] {
+                b' ' => return,
+                b'p' => {
+                    self.walls = self.walls.toggle();
+                    self.draw_start_prompt();
+                }
+                b'v' if self.host.is_none() => {
+                    self.players = if self.players == 1 { 2 } else { 1 };
+                    self.clamp_ai_snakes();
+                    self.reset();
+                    self.draw_start_prompt();
+                }
+                b'n' => {
+                    self.ai_snakes = (self.ai_snakes + 1) % (self.room() - self.players + 1);
+                    self.clamp_ai_snakes();
+                    self.reset();
+                    self.draw_start_prompt();
+                }
+                b'm' => {
+                    self.brain = self.brain.next();
+                    self.reset();
+                    self.draw_start_prompt();
+                }
+                b'o' => {
+                    self.autopilot = !self.autopilot;
+                    self.reset();
+                    self.draw_start_prompt();
+                }
+                b'c' => {
+                    self.pace.curve = self.pace.curve.next();
+                    self.draw_start_prompt();
+                }
+                b'e' => {
+                    self.pace.difficulty = self.pace.difficulty.next();
+                    self.draw_start_prompt();
+                }
+                b'g' => {
+                    self.gradient = !self.gradient;
+                    self.draw_start_prompt();
+                }
+                b'h' => {
+                    self.race_ghost = !self.race_ghost;
+                    self.draw_start_prompt();
+                }
+                b't' => {
+                    self.tron = !self.tron;
+                    self.clamp_ai_snakes();
+                    self.reset();
+                    self.draw_start_prompt();
+                }
+                b'b' => {
+                    self.best_of = if self.best_of >= 7 {
+                        1
+                    } else {
+                        self.best_of + 2
+                    };
+                    self.draw_start_prompt();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Draws the start prompt along with the current settings.
+    fn draw_start_prompt(&mut self) {
+        write!(
+            self.stdout,
+            "{}{}{}[p] walls: {}{}{}[v] players: {}{}{}[n] AI snakes: {}{}{}[m] AI: {}{}{}[o] autopilot: {}{}{}[c] speed: {}{}{}[e] difficulty: {}{}{}[g] gradient: {}{}{}[t] tron: {}{}{}[h] ghost: {}{}",
+            cursor::Goto(1, 1),
+            GAME_START_PROMPT,
+            cursor::Goto(1, 2),
+            self.walls.name(),
+            clear::UntilNewline,
+            cursor::Goto(1, 3),
+            self.players,
+            clear::UntilNewline,
+            cursor::Goto(1, 4),
+            self.ai_snakes,
+            clear::UntilNewline,
+            cursor::Goto(1, 5),
+            self.brain.name(),
+            clear::UntilNewline,
+            cursor::Goto(1, 6),
+            if self.autopilot { "on" } else { "off" },
+            clear::UntilNewline,
+            cursor::Goto(1, 7),
+            self.pace.curve.name(),
+            clear::UntilNewline,
+            cursor::Goto(1, 8),
+            self.pace.difficulty.name(),
+            clear::UntilNewline,
+            cursor::Goto(1, 9),
+            if self.gradient { "on" } else { "off" },
+            clear::UntilNewline,
+            cursor::Goto(1, 10),
+            if self.tron { "on" } else { "off" },
+            clear::UntilNewline,
+            cursor::Goto(1, 11),
+            match self.best_run() {
+                _ if !self.race_ghost => "off".to_string(),
+                Some(run) => format!("on, best run {}", run.score),
+                None => "on, no run recorded yet".to_string(),
+            },
+            clear::UntilNewline
+        )
+        .unwrap();
+        if self.versus() || self.host.is_some() {
+            write!(
+                self.stdout,
+                "{}[b] best of: {}{}",
+                cursor::Goto(1, 12),
+                self.best_of,
+                clear::UntilNewline
+            )
+            .unwrap();
+        }
+        if let Some(host) = &self.host {
+            write!(
+                self.stdout,
+                "{}Hosting on port {}, {} joined.{}",
+                cursor::Goto(1, 13),
+                host.port,
+                host.clients.len(),
+                clear::UntilNewline
+            )
+            .unwrap();
+        } else if self.players > 1 {
+            write!(
+                self.stdout,
+                "{}Player two steers with the arrow keys or 'i' 'j' 'k' 'l'.{}",
+                cursor::Goto(1, 13),
+                clear::UntilNewline
+            )
+            .unwrap();
+        }
+        let room = self.room();
+        if room < MAX_SNAKES {
+            write!(
+                self.stdout,
+                "{}Only {room} {} on the map.{}",
+                cursor::Goto(1, 14),
+                if room == 1 {
+                    "snake fits"
+                } else {
+                    "snakes fit"
+                },
+                clear::UntilNewline
+            )
+            .unwrap();
+        }
+        self.stdout.flush().unwrap();
+    }
+
+    /// Keep the number of players and AI snakes within what fits on every level, and give a
+    /// lone player someone to race in light-cycle mode if there is room.
+    fn clamp_ai_snakes(&mut self) {
+        let room = self.room();
+        self.players = self.players.min(room);
+        let min = usize::from(self.tron && self.players == 1);
+        self.ai_snakes = self.ai_snakes.max(min).min(room - self.players);
+    }
+
+    /// How many snakes fit on every level of the campaign.
+    fn room(&self) -> usize {
+        self.campaign
+            .iter()
+            .map(|level| level.room(MAX_SNAKES))
+            .min()
+            .unwrap_or(MAX_SNAKES)
+    }
+
+    /// Whether the game is played in rounds, as a match between several players or as light
+    /// cycles.
+    fn versus(&self) -> bool {
+        self.players > 1 || self.tron
+    }
+
+    /// The name a snake goes by in messages.
+    fn name(&self, i: usize) -> String {
+        if i < self.players {
+            format!("Player {}", i + 1)
+        } else {
+            format!("AI {}", i + 1 - self.players)
+        }
+    }
+
+    /// Count the round that just ended towards the match.
+    ///
+    /// Returns whether a snake has now won the match.
+    fn record_round(&mut self) -> bool {
+        match self.round_winner() {
+            Some(winner) if self.tron => {
+                self.scores[winner] += 1;
+                self.scores[winner] as u32 * 2 > self.best_of
+            }
+            Some(winner) => {
+                self.wins[winner] += 1;
+                self.wins[winner] * 2 > self.best_of
+            }
+            None => false,
+        }
+    }
+
+    /// The only player's snake left alive, if there is one. Light cycles race the AI snakes
+    /// as well.
+    fn round_winner(&self) -> Option<usize> {
+        let contenders = if self.tron {
+            self.snakes.len()
+        } else {
+            self.players
+        };
+        let mut alive = self
+            .snakes
+            .iter()
+            .enumerate()
+            .take(contenders)
+            .filter(|(_, s)| s.alive);
+        match (alive.next(), alive.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Show who won the round and wait for the players.
+    ///
+    /// Returns false if the game is supposed to be closed.
+    fn next_round(&mut self) -> bool {
+        let result = match self.round_winner() {
+            Some(i) => format!("{} wins the round", self.name(i)),
+            None => "Draw".to_string(),
+        };
+        self.broadcast(&Message::End(format!("{result}.")));
+        write!(
+            self.stdout,
+            "{}{}. Press 'space' for the next round or 'q' to exit.",
+            cursor::Goto(1, 1),
+            result
+        )
+        .unwrap();
+        self.draw_match_score();
+        self.stdout.flush().unwrap();
+
+        loop {
+            let mut buf = [0];
+            if self.stdin.read(&mut buf).unwrap() == 0 {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            match buf[0] {
+                b' ' => return true,
+                b'q' => return false,
+                _ => {}
+            }
+        }
+    }
+
+    /// Draws the rounds won and the food eaten by every player, or just the rounds won by
+    /// every light cycle.
+    fn draw_match_score(&mut self) {
+        write!(
+            self.stdout,
+            "{}",
+            cursor::Goto((self.width as u16 / 2) - 10, self.height as u16 / 2 + 1)
+        )
+        .unwrap();
+        if self.tron {
+            for i in 0..self.snakes.len() {
+                write!(self.stdout, "{}: {} won  ", self.name(i), self.scores[i]).unwrap();
+            }
+            return;
+        }
+        for i in 0..self.players {
+            write!(
+                self.stdout,
+                "P{}: {} won, {} eaten  ",
+                i + 1,
+                self.wins[i],
+                self.scores[i]
+            )
+            .unwrap();
+        }
+    }
+
+    /// Show `message` along with the final score and wait for the player.
+    ///
+    /// Returns whether to play again.
+    fn game_over(&mut self, message: &str) -> bool {
+        write!(self.stdout, "{}{}", cursor::Goto(1, 1), message).unwrap();
+        if self.versus() {
+            let winner = if self.tron {
+                (0..self.snakes.len()).max_by_key(|&i| self.scores[i])
+            } else {
+                (0..self.players).max_by_key(|&i| self.wins[i])
+            };
+            let winner = self.name(winner.unwrap());
+            write!(self.stdout, " {winner} wins the match!").unwrap();
+            self.draw_match_score();
+            self.broadcast(&Message::End(format!("{message} {winner} wins the match!")));
+        } else {
+            write!(
+                self.stdout,
+                "{}",
+                cursor::Goto((self.width as u16 / 2) - 2, self.height as u16 / 2 + 1)
+            )
+            .unwrap();
+            write!(self.stdout, "SCORE: {}", self.scores[0]).unwrap();
+        }
+        self.stdout.flush().unwrap();
+
+        loop {
+            // Repeatedly read a single byte.
+            let mut buf = [0];
+            self.stdin.read(&mut buf).unwrap();
+
+            match buf[0] {
+                b'r' => return true,
+                b'q' => return false,
+                _ => {}
+            }
+        }
+    }
+
+    fn draw_horizontal_line(&mut self, chr: &str, width: u16) {
+        for _ in 0..width {
+            self.stdout.write(chr.as_bytes()).unwrap();
+        }
+    }
+
+    /// Move the snake's food.
+    ///
+    /// The food only lands on empty cells of the map, as the rest of the board is walled off.
+    /// A map that fills the board reaches the range `wrap` maps portal crossings onto, so the
+    /// food is reachable in both wall modes. Returns false if the snakes cover every such
+    /// cell, which means the board is complete.
+    fn move_food(&mut self) -> bool {
+        let (left, top) = self.level_offset();
+        let level = &self.campaign[self.level];
+        let (width, height) = (level.width, level.height);
+
+        // Random cells are almost always free, so the whole board only has to be looked
+        // through once it is nearly full.
+        let zones = self.food_zones;
+        let mut spot = None;
+        for _ in 0..FOOD_TRIES {
+            let x = left + self.roll() % width;
+            let y = top + self.roll() % height;
+            if self.is_free(x, y, zones) {
+                spot = Some((x, y));
+                break;
+            }
+        }
+
+        let (x, y) = match spot {
+            Some(spot) => spot,
+            None => {
+                let free: Vec<(u16, u16)> = (top..top + height)
+                    .flat_map(|y| (left..left + width).map(move |x| (x, y)))
+                    .filter(|&(x, y)| self.is_free(x, y, zones))
+                    .collect();
+                if free.is_empty() {
+                    return false;
+                }
+                free[self.roll() as usize % free.len()]
+            }
+        };
+
+        let kind = self.food_table.pick(self.rand.read_u8() as u32);
+        self.food = Food {
+            x,
+            y,
+            kind,
+            expires: kind
+                .lifetime()
+                .map(|lifetime| self.tick + lifetime.as_secs() * self.speed),
+        };
+        true
+    }
+
+    /// A random number from two bytes of the randomizer.
+    fn roll(&mut self) -> u16 {
+        u16::from(self.rand.read_u8()) << 8 | u16::from(self.rand.read_u8())
+    }
+
+    /// Draws the snake's food in the colour of its kind.
+    fn draw_food(&mut self) {
+        if self.tron {
+            return;
+        }
+        write!(
+            self.stdout,
+            "{}{}{}{}",
+            cursor::Goto(self.food.x + 1, self.food.y + 1),
+            color::Fg(color::AnsiValue(self.food.kind.color())),
+            self.food.kind.glyph(),
+            color::Fg(color::Reset)
+        )
+        .unwrap();
+    }
+
+    /// Draws what changed about the snakes since the last move.
+    ///
+    /// Moving only changes the cells at either end of a snake, as the vacated tail cell is
+    /// erased while moving. With a gradient every shade shifts along the body, so those
+    /// snakes are drawn in full.
+    fn draw_snake(&mut self) {
+        for i in 0..self.snakes.len() {
+            if !self.snakes[i].alive {
+                continue;
+            }
+            if self.gradient {
+                self.draw_one_snake(i);
+                continue;
+            }
+
+            let length = self.snakes[i].body.len();
+            let mut ends = vec![0, 1, length - 2, length - 1];
+            ends.sort_unstable();
+            ends.dedup();
+            for k in ends {
+                self.draw_part(i, k);
+            }
+        }
+        write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
+    }
+
+    /// Draws a whole snake.
+    fn draw_one_snake(&mut self, i: usize) {
+        for k in 0..self.snakes[i].body.len() {
+            self.draw_part(i, k);
+        }
+        write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
+    }
+
+    /// Draws the `k`th part of the `i`th snake, counting from the tail, with corners at its
+    /// turns, its head pointing the way it moves and a tail of its own.
+    fn draw_part(&mut self, i: usize, k: usize) {
+        let snake = &self.snakes[i];
+        let part = &snake.body[k];
+        let length = snake.body.len();
+
+        let color = if self.gradient {
+            let shades = SNAKE_GRADIENTS[i % SNAKE_GRADIENTS.len()];
+            shades[(length - 1 - k) * shades.len() / length]
+        } else {
+            SNAKE_COLORS[i % SNAKE_COLORS.len()]
+        };
+
+        // Each part stores the direction the head moved in to get there, which also works
+        // across portal walls where neighbouring parts are far apart on screen.
+        let glyph = match snake.body.get(k + 1) {
+            None => head_glyph(part.direction),
+            Some(_) if k == 0 => SNAKE_TAIL,
+            Some(next) => body_glyph(part.direction.opposite(), next.direction),
+        };
+        write!(
+            self.stdout,
+            "{}{}{}",
+            cursor::Goto(part.x + 1, part.y + 1),
+            color::Fg(color::AnsiValue(color)),
+            glyph
+        )
+        .unwrap();
+    }
+
+    /// Draws the score and length of every snake, the speed and the time spent on the level
+    /// on the line below the board. Whatever does not fit in the board's width is left out.
+    fn draw_hud(&mut self) {
+        let elapsed = self.clock.elapsed().as_secs();
+        let mut items: Vec<(Option<u8>, String)> = (0..self.snakes.len())
+            .map(|i| {
+                let name = if i < self.players {
+                    format!("P{}", i + 1)
+                } else {
+                    format!("AI{}", i + 1 - self.players)
+                };
+                let color = SNAKE_COLORS[i % SNAKE_COLORS.len()];
+                let text = format!(
+                    "{name} {} ({} long)",
+                    self.scores[i],
+                    self.snakes[i].body.len()
+                );
+                (Some(color), text)
+            })
+            .collect();
+        if let Some(ghost) = &self.ghost {
+            items.push((Some(GHOST_COLOR), format!("Ghost {}", ghost.score())));
+        }
+        items.push((None, format!("{}/s", self.speed)));
+        items.push((None, format!("{}:{:02}", elapsed / 60, elapsed % 60)));
+        items.push((None, "[p] pause".to_string()));
+
+        write!(
+            self.stdout,
+            "{}{}",
+            cursor::Goto(1, self.height as u16 + 1),
+            clear::CurrentLine
+        )
+        .unwrap();
+
+        let mut used = 0;
+        for (color, text) in items {
+            let width = text.chars().count() + 2;
+            if used + width > self.width {
+                break;
+            }
+            used += width;
+            match color {
+                Some(color) => write!(self.stdout, "{}", color::Fg(color::AnsiValue(color))),
+                None => write!(self.stdout, "{}", color::Fg(color::Reset)),
+            }
+            .unwrap();
+            write!(self.stdout, "{text}  ").unwrap();
+        }
+        write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
+    }
+
+    /// Draw what a cell shows with nothing on it: the border, a wall, a portal or a blank.
+    /// Cells a snake, the food or the ghost leave are erased with this.
+    fn draw_tile(&mut self, x: u16, y: u16) {
+        let border = x == 0 || y == 0 || x == self.width as u16 - 1 || y == self.height as u16 - 1;
+        let goto = cursor::Goto(x + 1, y + 1);
+        let (red, cyan, reset) = (
+            color::Fg(color::Red),
+            color::Fg(color::Cyan),
+            color::Fg(color::Reset),
+        );
+        match self.tile(x, y) {
+            _ if border && self.walls == Walls::Portal => {
+                write!(self.stdout, "{goto}{cyan}{PORTAL_BORDER}{reset}")
+            }
+            _ if border => write!(self.stdout, "{goto}{red}{BORDER}{reset}"),
+            Tile::Wall => write!(self.stdout, "{goto}{red}{BORDER}{reset}"),
+            Tile::Portal(n) => write!(self.stdout, "{goto}{cyan}{n}{reset}"),
+            _ => write!(self.stdout, "{goto} "),
+        }
+        .unwrap();
     }
 
     /// Draws the game walls.
@@ -421,59 +1897,207 @@ impl<R: Read, W: Write> Game<R, W> {
         let width: u16 = self.width as u16;
         let height: u16 = self.height as u16;
 
-        write!(self.stdout, "{}", color::Fg(color::Red)).unwrap();
+        // Portal walls get their own colour and glyph so it is clear they can be crossed.
+        let border = match self.walls {
+            Walls::Solid => {
+                write!(self.stdout, "{}", color::Fg(color::Red)).unwrap();
+                BORDER
+            }
+            Walls::Portal => {
+                write!(self.stdout, "{}", color::Fg(color::Cyan)).unwrap();
+                PORTAL_BORDER
+            }
+        };
 
-        write!(self.stdout, "{}{}", cursor::Goto(1, 1), BORDER).unwrap();
+        write!(self.stdout, "{}{}", cursor::Goto(1, 1), border).unwrap();
         write!(self.stdout, "{}", cursor::Goto(2, 1)).unwrap();
-        self.draw_horizontal_line(BORDER, width - 2);
-        write!(self.stdout, "{}{}", cursor::Goto(width, 1), BORDER).unwrap();
+        self.draw_horizontal_line(border, width - 2);
+        write!(self.stdout, "{}{}", cursor::Goto(width, 1), border).unwrap();
 
         for y in 1..height {
-            write!(self.stdout, "{}{}", cursor::Goto(1, y + 1), BORDER).unwrap();
+            write!(self.stdout, "{}{}", cursor::Goto(1, y + 1), border).unwrap();
             write!(
                 self.stdout,
                 "{}{}",
                 cursor::Goto(self.width as u16, y + 1),
-                BORDER
+                border
             )
             .unwrap();
         }
 
-        write!(self.stdout, "{}{}", cursor::Goto(1, height), BORDER).unwrap();
+        write!(self.stdout, "{}{}", cursor::Goto(1, height), border).unwrap();
         write!(self.stdout, "{}", cursor::Goto(2, height)).unwrap();
-        self.draw_horizontal_line(BORDER, width - 2);
-        write!(self.stdout, "{}{}", cursor::Goto(width, height), BORDER).unwrap();
+        self.draw_horizontal_line(border, width - 2);
+        write!(self.stdout, "{}{}", cursor::Goto(width, height), border).unwrap();
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                if !self.tile(x, y).is_open() {
+                    self.draw_tile(x, y);
+                }
+            }
+        }
+
+        let level = &self.campaign[self.level];
+        write!(
+            self.stdout,
+            "{}{} Level {}: {} ",
+            cursor::Goto(3, height),
+            color::Fg(color::Reset),
+            self.level + 1,
+            level.name
+        )
+        .unwrap();
 
         write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
     }
 }
 
 /// Initializes the game.
-fn init(width: usize, height: usize) {
+fn init(options: &Options) {
     let stdout = stdout();
     let mut stdout = stdout.lock().into_raw_mode().unwrap();
-    let stdin = async_stdin();
+    let mut stdin = async_stdin();
 
     write!(stdout, "{}{}", clear::All, cursor::Goto(1, 1)).unwrap();
-    stdout.flush().unwrap();
 
-    let mut game = Game {
-        width,
-        height,
-        stdin,
-        stdout,
-        snake: Snake {
-            direction: Direction::Right,
-            body: VecDeque::new(),
+    if let Some(path) = &options.edit {
+        let (width, height) = options.board_size();
+        if width < MIN_WIDTH || height < MIN_HEIGHT {
+            write!(
+                stdout,
+                "The board is {width}x{height} but the editor needs at least \
+                 {MIN_WIDTH}x{MIN_HEIGHT}.\n\rPress any key to exit."
+            )
+            .unwrap();
+            wait_for_key(&mut stdout, &mut stdin);
+            return;
+        }
+        match Editor::open(path, width, height, &mut stdin, &mut stdout) {
+            Ok(mut editor) => editor.run(),
+            Err(e) => {
+                write!(
+                    stdout,
+                    "Could not open {path}: {e}.\n\rPress any key to exit."
+                )
+                .unwrap();
+                wait_for_key(&mut stdout, &mut stdin);
+                return;
+            }
+        }
+        write!(
+            stdout,
+            "{}{}{}",
+            clear::All,
+            style::Reset,
+            cursor::Goto(1, 1)
+        )
+        .unwrap();
+        stdout.flush().unwrap();
+        return;
+    }
+
+    let mut peer = match &options.join {
+        Some(address) => match TcpStream::connect(address).and_then(Peer::new) {
+            Ok(peer) => Some(peer),
+            Err(e) => {
+                write!(
+                    stdout,
+                    "Could not join {address}: {e}.\n\rPress any key to exit."
+                )
+                .unwrap();
+                wait_for_key(&mut stdout, &mut stdin);
+                return;
+            }
         },
-        food: Food { x: 0, y: 0 },
-        score: 0,
-        speed: 0,
-        rand: Randomizer::new(0),
+        None => None,
     };
 
-    game.reset();
-    game.start();
+    let host = match options.host.map(Host::bind) {
+        Some(Ok(host)) => Some(host),
+        Some(Err(e)) => {
+            write!(
+                stdout,
+                "Could not host a game: {e}.\n\rPress any key to exit."
+            )
+            .unwrap();
+            wait_for_key(&mut stdout, &mut stdin);
+            return;
+        }
+        None => None,
+    };
+
+    // A client gets its levels from the host, so it only needs a stand-in until then.
+    let campaign = match &options.level {
+        _ if peer.is_some() => vec![Level::blank("")],
+        Some(path) => match std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|src| Level::parse(&src))
+        {
+            Ok(level) => vec![level],
+            Err(e) => {
+                write!(
+                    stdout,
+                    "Could not load level {path}: {e}.\n\rPress any key to exit."
+                )
+                .unwrap();
+                wait_for_key(&mut stdout, &mut stdin);
+                return;
+            }
+        },
+        None => Level::campaign(),
+    };
+
+    let food_table = match options.food.as_deref().map(SpawnTable::parse) {
+        Some(Ok(table)) => table,
+        Some(Err(e)) => {
+            write!(stdout, "Invalid food table: {e}.\n\rPress any key to exit.").unwrap();
+            wait_for_key(&mut stdout, &mut stdin);
+            return;
+        }
+        None => SpawnTable::default(),
+    };
+
+    let (width, height) = options.board_size();
+    let min_width = campaign
+        .iter()
+        .map(|level| level.width as usize + 2)
+        .fold(MIN_WIDTH, usize::max);
+    let min_height = campaign
+        .iter()
+        .map(|level| level.height as usize + 2)
+        .fold(MIN_HEIGHT, usize::max);
+    if width < min_width || height < min_height {
+        write!(
+            stdout,
+            "The board is {width}x{height} but snake needs at least {min_width}x{min_height}.\n\r\
+             Resize the terminal and try again. Press any key to exit."
+        )
+        .unwrap();
+        wait_for_key(&mut stdout, &mut stdin);
+        return;
+    }
+
+    stdout.flush().unwrap();
+
+    let mut game = Game::new(width, height, campaign, stdin, stdout);
+    game.walls = options.walls;
+    game.autopilot = options.autopilot;
+    game.tron = options.tron;
+    game.food_table = food_table;
+    game.pace = options.pace;
+    game.gradient = options.gradient;
+    game.host = host;
+    game.race_ghost = options.ghost;
+    game.runs = Some(replay::load());
+
+    if let Some(peer) = &mut peer {
+        game.follow(peer);
+    } else {
+        game.clamp_ai_snakes();
+        game.reset();
+        game.start();
+    }
 
     write!(
         game.stdout,
@@ -486,7 +2110,112 @@ fn init(width: usize, height: usize) {
     game.stdout.flush().unwrap();
 }
 
+/// Map a position on a portal wall of a `width` by `height` board to the matching cell on the
+/// opposite side.
+///
+/// Positions inside the board, and any position when the walls are solid, are kept.
+fn wrap_position(walls: Walls, width: u16, height: u16, x: u16, y: u16) -> (u16, u16) {
+    if walls == Walls::Solid {
+        return (x, y);
+    }
+
+    let x = match x {
+        0 => width - 2,
+        x if x == width - 1 => 1,
+        x => x,
+    };
+    let y = match y {
+        0 => height - 2,
+        y if y == height - 1 => 1,
+        y => y,
+    };
+    (x, y)
+}
+
+/// Drain all pending input and parse it into keys, arrow keys included.
+fn read_keys(stdin: &mut impl Read) -> Vec<Key> {
+    let mut bytes = Vec::new();
+    let mut buf = [0; 32];
+
+    loop {
+        let n = stdin.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&buf[..n]);
+    }
+
+    let mut keys = Vec::new();
+    let mut iter = bytes.into_iter().map(Ok);
+    while let Some(Ok(b)) = iter.next() {
+        if let Ok(Event::Key(key)) = event::parse_event(b, &mut iter) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// Map a key to the snake it steers and the direction it asks for.
+///
+/// Player one uses 'w' 'a' 's' 'd'. With two players, player two has the arrow keys and
+/// 'i' 'j' 'k' 'l'; otherwise the arrow keys steer player one as well.
+fn steering(key: Key, players: usize) -> Option<(usize, Direction)> {
+    let second = usize::from(players > 1);
+    match key {
+        Key::Char('w') => Some((0, Direction::Up)),
+        Key::Char('s') => Some((0, Direction::Down)),
+        Key::Char('a') => Some((0, Direction::Left)),
+        Key::Char('d') => Some((0, Direction::Right)),
+        Key::Up => Some((second, Direction::Up)),
+        Key::Down => Some((second, Direction::Down)),
+        Key::Left => Some((second, Direction::Left)),
+        Key::Right => Some((second, Direction::Right)),
+        Key::Char('i') if players > 1 => Some((1, Direction::Up)),
+        Key::Char('k') if players > 1 => Some((1, Direction::Down)),
+        Key::Char('j') if players > 1 => Some((1, Direction::Left)),
+        Key::Char('l') if players > 1 => Some((1, Direction::Right)),
+        _ => None,
+    }
+}
+
+/// The glyph for a snake's head moving in `direction`.
+fn head_glyph(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => SNAKE_HEAD_UP,
+        Direction::Down => SNAKE_HEAD_DOWN,
+        Direction::Left => SNAKE_HEAD_LEFT,
+        Direction::Right => SNAKE_HEAD_RIGHT,
+    }
+}
+
+/// The glyph for a body part joined to the previous part on one side and the next on another.
+fn body_glyph(previous: Direction, next: Direction) -> &'static str {
+    use Direction::{Down, Left, Right, Up};
+
+    match (previous, next) {
+        (Up, Down) | (Down, Up) => VERTICAL_SNAKE_BODY,
+        (Left, Right) | (Right, Left) => HORIZONTAL_SNAKE_BODY,
+        (Down, Right) | (Right, Down) => SNAKE_TOP_LEFT,
+        (Down, Left) | (Left, Down) => SNAKE_TOP_RIGHT,
+        (Up, Right) | (Right, Up) => SNAKE_BOTTOM_LEFT,
+        (Up, Left) | (Left, Up) => SNAKE_BOTTOM_RIGHT,
+        // Only a snake folded back onto itself has both sides the same way.
+        (Up | Down, _) => VERTICAL_SNAKE_BODY,
+        (Left | Right, _) => HORIZONTAL_SNAKE_BODY,
+    }
+}
+
+/// Flush the output and block until a key is pressed.
+fn wait_for_key<R: Read, W: Write>(stdout: &mut W, stdin: &mut R) {
+    stdout.flush().unwrap();
+
+    let mut buf = [0];
+    while stdin.read(&mut buf).unwrap() == 0 {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub fn snake_game() {
-    init(80, 50);
+    init(&Options::from_args());
 }
@@ -0,0 +1,455 @@
+//! Computer-controlled snakes.
+//!
+//! A brain only picks the direction to turn to. Moving, growing and crashing go through the
+//! same code as the players' snakes.
+
+use super::{wrap_position, BodyPart, Direction, Walls};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+/// How an AI snake picks its moves, from the easiest to beat to the hardest.
+#[derive(PartialEq, Clone, Copy)]
+pub enum Brain {
+    /// Heads straight for the food, only avoiding the cell right in front of it.
+    Greedy,
+    /// Follows the shortest path to the food, but only if it can still reach its own tail
+    /// after eating. Otherwise it chases its tail until the food is safe to go for.
+    AStar,
+    /// Rates every move by how much room is left behind it, keeps away from the other heads
+    /// and only then looks at the food.
+    Cautious,
+    /// Follows a Hamiltonian cycle through the board, cutting it short towards the food
+    /// while there is room. Steers player one's snake in autopilot mode.
+    Autopilot,
+}
+
+impl Brain {
+    pub fn next(self) -> Brain {
+        match self {
+            Brain::Greedy => Brain::AStar,
+            Brain::AStar => Brain::Cautious,
+            Brain::Cautious | Brain::Autopilot => Brain::Greedy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Brain::Greedy => "easy (greedy)",
+            Brain::AStar => "normal (A*)",
+            Brain::Cautious => "hard (cautious)",
+            Brain::Autopilot => "autopilot",
+        }
+    }
+
+    /// Pick the direction the snake should move in next.
+    pub fn decide(self, view: &View) -> Direction {
+        match self {
+            Brain::Greedy => greedy(view),
+            Brain::AStar => a_star(view),
+            Brain::Cautious => cautious(view),
+            Brain::Autopilot => autopilot(view),
+        }
+    }
+
+    /// Pick the direction a light cycle should move in next. There is no food to go for, so
+    /// all that matters is staying alive.
+    pub fn survive(self, view: &View) -> Direction {
+        match self {
+            Brain::Greedy => straight_on(view),
+            Brain::AStar | Brain::Cautious | Brain::Autopilot => most_room(view),
+        }
+    }
+}
+
+/// What an AI snake knows about the board when picking its move.
+pub struct View<'a> {
+    pub width: u16,
+    pub height: u16,
+    pub walls: Walls,
+    /// Cells that kill a snake moving into them: walls and every body part except the tails,
+    /// which move out of the way.
+    pub blocked: &'a [bool],
+    /// Cells the other snakes' heads can move into on this tick.
+    pub contested: &'a [bool],
+    /// The snake's own body, from the tail to the head.
+    pub body: &'a VecDeque<BodyPart>,
+    pub direction: Direction,
+    pub food: (u16, u16),
+    /// Each cell's position along the map's Hamiltonian cycle, empty if there is none.
+    pub cycle: &'a [u32],
+    /// How many cells the cycle runs through.
+    pub cycle_length: u32,
+}
+
+impl View<'_> {
+    fn index(&self, (x, y): (u16, u16)) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    fn head(&self) -> (u16, u16) {
+        let head = self.body.back().unwrap();
+        (head.x, head.y)
+    }
+
+    fn tail(&self) -> (u16, u16) {
+        let tail = self.body.front().unwrap();
+        (tail.x, tail.y)
+    }
+
+    /// The cell reached by moving one step from `cell`.
+    fn step(&self, (x, y): (u16, u16), direction: Direction) -> (u16, u16) {
+        let (x, y) = match direction {
+            Direction::Up => (x, y - 1),
+            Direction::Down => (x, y + 1),
+            Direction::Left => (x - 1, y),
+            Direction::Right => (x + 1, y),
+        };
+        wrap_position(self.walls, self.width, self.height, x, y)
+    }
+
+    /// Free cells next to `cell` along with the direction leading to each.
+    fn neighbours(&self, cell: (u16, u16), blocked: &[bool]) -> Vec<(Direction, (u16, u16))> {
+        DIRECTIONS
+            .iter()
+            .map(|&d| (d, self.step(cell, d)))
+            .filter(|&(_, next)| !blocked[self.index(next)])
+            .collect()
+    }
+
+    /// Moves from the head that do not crash right away.
+    fn safe_moves(&self) -> Vec<(Direction, (u16, u16))> {
+        let reverse = self.direction.opposite();
+        self.neighbours(self.head(), self.blocked)
+            .into_iter()
+            .filter(|&(d, _)| d != reverse)
+            .collect()
+    }
+
+    /// The direction of the step from `from` to the neighbouring cell `to`.
+    fn direction_to(&self, from: (u16, u16), to: (u16, u16)) -> Direction {
+        *DIRECTIONS
+            .iter()
+            .find(|&&d| self.step(from, d) == to)
+            .unwrap()
+    }
+
+    /// Manhattan distance, taking the short way through portal walls.
+    fn distance(&self, a: (u16, u16), b: (u16, u16)) -> u32 {
+        let (dx, dy) = (a.0.abs_diff(b.0) as u32, a.1.abs_diff(b.1) as u32);
+        match self.walls {
+            Walls::Solid => dx + dy,
+            Walls::Portal => {
+                let (w, h) = (self.width as u32 - 2, self.height as u32 - 2);
+                dx.min(w - dx) + dy.min(h - dy)
+            }
+        }
+    }
+
+    /// Breadth-first distances from `from` to every cell, `u32::MAX` where unreachable.
+    fn distances(&self, from: (u16, u16), blocked: &[bool]) -> Vec<u32> {
+        let mut dist = vec![u32::MAX; blocked.len()];
+        let mut queue = VecDeque::from([from]);
+        dist[self.index(from)] = 0;
+
+        while let Some(cell) = queue.pop_front() {
+            let d = dist[self.index(cell)];
+            for (_, next) in self.neighbours(cell, blocked) {
+                let i = self.index(next);
+                if dist[i] == u32::MAX {
+                    dist[i] = d + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        dist
+    }
+
+    /// The shortest path from `from` to `to`, without `from` itself.
+    fn path(&self, from: (u16, u16), to: (u16, u16), blocked: &[bool]) -> Option<Vec<(u16, u16)>> {
+        let mut came_from = vec![None; blocked.len()];
+        let mut cost = vec![u32::MAX; blocked.len()];
+        let mut open = BinaryHeap::new();
+
+        cost[self.index(from)] = 0;
+        open.push(Reverse((self.distance(from, to), 0, from)));
+
+        while let Some(Reverse((_, g, cell))) = open.pop() {
+            if cell == to {
+                let mut path = vec![to];
+                let mut cell = to;
+                while let Some(prev) = came_from[self.index(cell)] {
+                    if prev == from {
+                        break;
+                    }
+                    path.push(prev);
+                    cell = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if g > cost[self.index(cell)] {
+                continue;
+            }
+
+            for (_, next) in self.neighbours(cell, blocked) {
+                let i = self.index(next);
+                if g + 1 < cost[i] {
+                    cost[i] = g + 1;
+                    came_from[i] = Some(cell);
+                    open.push(Reverse((g + 1 + self.distance(next, to), g + 1, next)));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Whether the snake could still reach its own tail after following `path` and eating
+    /// the food at its end.
+    fn safe_after(&self, path: &[(u16, u16)]) -> bool {
+        let mut blocked = self.blocked.to_vec();
+        for part in self.body {
+            blocked[self.index((part.x, part.y))] = false;
+        }
+
+        let cells: Vec<(u16, u16)> = self
+            .body
+            .iter()
+            .map(|part| (part.x, part.y))
+            .chain(path.iter().copied())
+            .collect();
+        let body = &cells[cells.len().saturating_sub(self.body.len() + 1)..];
+        for &cell in body {
+            blocked[self.index(cell)] = true;
+        }
+
+        let (tail, head) = (body[0], body[body.len() - 1]);
+        blocked[self.index(tail)] = false;
+        self.path(head, tail, &blocked).is_some()
+    }
+}
+
+fn greedy(view: &View) -> Direction {
+    view.safe_moves()
+        .into_iter()
+        .min_by_key(|&(_, cell)| view.distance(cell, view.food))
+        .map_or(view.direction, |(d, _)| d)
+}
+
+fn a_star(view: &View) -> Direction {
+    let head = view.head();
+
+    if let Some(path) = view.path(head, view.food, view.blocked) {
+        if view.safe_after(&path) {
+            return view.direction_to(head, path[0]);
+        }
+    }
+
+    // Chase the tail to buy time until the food can be reached safely.
+    let tail = view.tail();
+    if tail != head {
+        let mut blocked = view.blocked.to_vec();
+        blocked[view.index(tail)] = false;
+        if let Some(path) = view.path(head, tail, &blocked) {
+            let direction = view.direction_to(head, path[0]);
+            if direction != view.direction.opposite() {
+                return direction;
+            }
+        }
+    }
+
+    cautious(view)
+}
+
+fn cautious(view: &View) -> Direction {
+    let length = view.body.len() + 1;
+
+    view.safe_moves()
+        .into_iter()
+        .max_by_key(|&(_, cell)| {
+            let mut blocked = view.blocked.to_vec();
+            blocked[view.index(cell)] = true;
+
+            let dist = view.distances(cell, &blocked);
+            let room = dist.iter().filter(|&&d| d != u32::MAX).count();
+            let to_food = if cell == view.food {
+                0
+            } else {
+                dist[view.index(view.food)]
+            };
+
+            (
+                room >= length,
+                !view.contested[view.index(cell)],
+                Reverse(to_food),
+                room,
+            )
+        })
+        .map_or(view.direction, |(d, _)| d)
+}
+
+/// Keeps going straight and only turns to dodge what is right in front of it.
+fn straight_on(view: &View) -> Direction {
+    let moves = view.safe_moves();
+    if moves.iter().any(|&(d, _)| d == view.direction) {
+        return view.direction;
+    }
+    moves.first().map_or(view.direction, |&(d, _)| d)
+}
+
+/// Moves towards the most room, keeping away from the other heads when that costs nothing.
+fn most_room(view: &View) -> Direction {
+    view.safe_moves()
+        .into_iter()
+        .max_by_key(|&(_, cell)| {
+            let mut blocked = view.blocked.to_vec();
+            blocked[view.index(cell)] = true;
+
+            let room = view
+                .distances(cell, &blocked)
+                .iter()
+                .filter(|&&d| d != u32::MAX)
+                .count();
+            (room, !view.contested[view.index(cell)])
+        })
+        .map_or(view.direction, |(d, _)| d)
+}
+
+/// Never cutting past its own tail keeps the body in cycle order, so following the cycle is
+/// always safe and the snake ends up filling the board. Shortcuts are only taken while at
+/// least half of the board is free.
+fn autopilot(view: &View) -> Direction {
+    let head = view.head();
+    let tail = view.tail();
+    let size = view.cycle_length;
+    let position = |cell| view.cycle.get(view.index(cell)).copied();
+    let (Some(from), Some(to_tail), Some(to_food)) =
+        (position(head), position(tail), position(view.food))
+    else {
+        return cautious(view);
+    };
+    if from == u32::MAX || to_tail == u32::MAX {
+        return cautious(view);
+    }
+    let along = |to: u32| (to + size - from) % size;
+    let (to_tail, to_food) = (i64::from(along(to_tail)), i64::from(along(to_food)));
+
+    let length = view.body.len() as i64;
+    let free = i64::from(size) - length - 1;
+    let mut shortcut = to_tail - length - 3;
+    if free < i64::from(size) / 2 {
+        shortcut = 0;
+    } else if to_food < to_tail {
+        shortcut -= 1;
+        if (to_tail - shortcut) * 4 > free {
+            shortcut -= 10;
+        }
+    }
+    let shortcut = shortcut.min(to_food).max(1);
+
+    view.safe_moves()
+        .into_iter()
+        .map(|(d, cell)| (d, i64::from(along(view.cycle[view.index(cell)]))))
+        .filter(|&(_, ahead)| ahead <= shortcut)
+        .max_by_key(|&(_, ahead)| ahead)
+        .map_or_else(|| cautious(view), |(d, _)| d)
+}
+
+/// A Hamiltonian cycle through the cells inside the walls of a `width` by `height` board, as
+/// each cell's position along it. Cells on the walls get `u32::MAX`.
+///
+/// The cycle runs along the top row, snakes back and forth over the rows below it without
+/// their first cell and comes back up along the first column. That needs an even number of
+/// rows, so the board is turned on its side if only the number of columns is even. Returns
+/// an empty list if both are odd, as there is no cycle then.
+pub fn hamiltonian_cycle(width: u16, height: u16) -> Vec<u32> {
+    let (w, h) = (width as usize - 2, height as usize - 2);
+    let turned = h % 2 == 1;
+    if turned && w % 2 == 1 {
+        return Vec::new();
+    }
+    let (cols, rows) = if turned { (h, w) } else { (w, h) };
+
+    let mut cells: Vec<(usize, usize)> = (0..cols).map(|c| (c, 0)).collect();
+    for r in 1..rows {
+        if r % 2 == 1 {
+            cells.extend((1..cols).rev().map(|c| (c, r)));
+        } else {
+            cells.extend((1..cols).map(|c| (c, r)));
+        }
+    }
+    cells.extend((1..rows).rev().map(|r| (0, r)));
+
+    let mut cycle = vec![u32::MAX; width as usize * height as usize];
+    for (i, (c, r)) in (0..).zip(cells) {
+        let (x, y) = if turned { (r, c) } else { (c, r) };
+        cycle[(y + 1) * width as usize + x + 1] = i;
+    }
+    cycle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that the cycle visits every cell inside the walls once, moving to a neighbouring
+    /// cell on every step and from the last cell back to the first.
+    fn assert_cycle(width: u16, height: u16) {
+        let cycle = hamiltonian_cycle(width, height);
+        let (w, h) = (width as usize, height as usize);
+        assert_eq!(cycle.len(), w * h, "{width}x{height}");
+
+        let inside = (w - 2) * (h - 2);
+        let mut cells = vec![None; inside];
+        for y in 0..h {
+            for x in 0..w {
+                let i = cycle[y * w + x];
+                let wall = x == 0 || y == 0 || x == w - 1 || y == h - 1;
+                if wall {
+                    assert_eq!(i, u32::MAX, "{width}x{height} at {x},{y}");
+                    continue;
+                }
+                let slot = &mut cells[i as usize];
+                assert!(slot.is_none(), "{width}x{height}: {i} is there twice");
+                *slot = Some((x, y));
+            }
+        }
+
+        let cells: Vec<(usize, usize)> = cells.into_iter().map(Option::unwrap).collect();
+        for k in 0..inside {
+            let (a, b) = (cells[k], cells[(k + 1) % inside]);
+            assert_eq!(
+                a.0.abs_diff(b.0) + a.1.abs_diff(b.1),
+                1,
+                "{width}x{height}: step {k} jumps from {a:?} to {b:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cycles_on_even_sizes() {
+        for (width, height) in [(4, 4), (6, 4), (4, 7), (7, 4), (30, 15), (15, 30), (80, 23)] {
+            assert_cycle(width, height);
+        }
+    }
+
+    #[test]
+    fn no_cycle_on_odd_sizes() {
+        for (width, height) in [(5, 5), (3, 5), (31, 15)] {
+            assert!(
+                hamiltonian_cycle(width, height).is_empty(),
+                "{width}x{height}"
+            );
+        }
+    }
+}
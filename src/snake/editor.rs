@@ -0,0 +1,423 @@
+//! An editor for snake levels.
+//!
+//! The map is painted a cell at a time with a brush and saved in the level file format, so
+//! whatever it makes can be played with `--level`. It keeps the header lines of a file it
+//! opens as they are, only adding a name if there is none.
+
+use super::{head_glyph, read_keys, Direction, Game, Level};
+use crate::graphics::{
+    clear, color, cursor, event::Key, style, BORDER, FOOD_ZONE, NO_FOOD, OFF_MAP,
+};
+use std::{
+    fs, io,
+    io::{Read, Write},
+    path::Path,
+    time::Duration,
+};
+
+/// What painting a cell puts there.
+#[derive(PartialEq, Clone, Copy)]
+enum Brush {
+    Wall,
+    /// The next free end of a portal.
+    Portal,
+    Spawn,
+    FoodZone,
+    NoFood,
+    Erase,
+}
+
+impl Brush {
+    const ALL: [Brush; 6] = [
+        Brush::Wall,
+        Brush::Portal,
+        Brush::Spawn,
+        Brush::FoodZone,
+        Brush::NoFood,
+        Brush::Erase,
+    ];
+
+    /// The key that picks the brush.
+    fn key(self) -> char {
+        match self {
+            Brush::Wall => '1',
+            Brush::Portal => '2',
+            Brush::Spawn => '3',
+            Brush::FoodZone => '4',
+            Brush::NoFood => '5',
+            Brush::Erase => '0',
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Brush::Wall => "wall",
+            Brush::Portal => "portal",
+            Brush::Spawn => "spawn",
+            Brush::FoodZone => "food zone",
+            Brush::NoFood => "no food",
+            Brush::Erase => "erase",
+        }
+    }
+}
+
+pub struct Editor<R, W> {
+    /// The level file being edited.
+    path: String,
+    /// The lines above the map, like the level's name and target.
+    header: Vec<String>,
+    /// The size of the map.
+    width: u16,
+    height: u16,
+    /// The map, row by row, in the characters of the level file format.
+    cells: Vec<char>,
+    /// The size of the board the map is shown and test-played on.
+    board_width: usize,
+    board_height: usize,
+    /// The cell the next paint goes to, in map coordinates.
+    cursor: (u16, u16),
+    brush: Brush,
+    /// The direction new spawn points face.
+    facing: Direction,
+    /// Whether there are changes that are not saved yet.
+    dirty: bool,
+    /// Shown in place of the brushes until the next key, like why the level cannot be played.
+    status: String,
+    /// Standard input.
+    stdin: R,
+    /// Standard output.
+    stdout: W,
+}
+
+impl<R: Read, W: Write> Editor<R, W> {
+    /// Open a level file, or start a blank map filling the board if it does not exist yet.
+    pub fn open(
+        path: &str,
+        board_width: usize,
+        board_height: usize,
+        stdin: R,
+        stdout: W,
+    ) -> Result<Editor<R, W>, String> {
+        let src = match fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut lines = src.lines();
+        let mut header: Vec<String> = lines
+            .by_ref()
+            .take_while(|line| line.trim() != "---")
+            .map(str::to_string)
+            .collect();
+        if !header
+            .iter()
+            .any(|line| line.trim_start().starts_with("name:"))
+        {
+            let stem = Path::new(path).file_stem().unwrap_or_default();
+            header.insert(0, format!("name: {}", stem.to_string_lossy()));
+        }
+
+        let rows: Vec<Vec<char>> = lines.map(|line| line.chars().collect()).collect();
+        let mut width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut height = rows.len();
+        if width == 0 {
+            width = board_width - 2;
+            height = board_height - 2;
+        }
+        if width > board_width - 2 || height > board_height - 2 {
+            return Err(format!(
+                "the map is {width}x{height} but only {}x{} fits on the board",
+                board_width - 2,
+                board_height - 2
+            ));
+        }
+
+        let mut cells = vec!['.'; width * height];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                cells[y * width + x] = c;
+            }
+        }
+
+        Ok(Editor {
+            path: path.to_string(),
+            header,
+            width: width as u16,
+            height: height as u16,
+            cells,
+            board_width,
+            board_height,
+            cursor: (width as u16 / 2, height as u16 / 2),
+            brush: Brush::Wall,
+            facing: Direction::Right,
+            dirty: false,
+            status: String::new(),
+            stdin,
+            stdout,
+        })
+    }
+
+    /// Edit until the player quits.
+    pub fn run(&mut self) {
+        write!(self.stdout, "{}", cursor::Hide).unwrap();
+        self.draw();
+        let mut quitting = false;
+
+        loop {
+            let keys = read_keys(&mut self.stdin);
+            if keys.is_empty() {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+
+            for key in keys {
+                self.status.clear();
+                let (x, y) = self.cursor;
+                match key {
+                    Key::Char('q') if !self.dirty || quitting => return,
+                    Key::Char('q') => {
+                        self.status = "There are unsaved changes. Press 'q' again to quit anyway."
+                            .to_string();
+                        quitting = true;
+                        self.draw_help();
+                        continue;
+                    }
+                    Key::Up | Key::Char('w') => self.cursor.1 = y.saturating_sub(1),
+                    Key::Down | Key::Char('s') => self.cursor.1 = (y + 1).min(self.height - 1),
+                    Key::Left | Key::Char('a') => self.cursor.0 = x.saturating_sub(1),
+                    Key::Right | Key::Char('d') => self.cursor.0 = (x + 1).min(self.width - 1),
+                    Key::Char(' ') => self.paint(),
+                    Key::Char('r') => {
+                        self.facing = match self.facing {
+                            Direction::Right => Direction::Down,
+                            Direction::Down => Direction::Left,
+                            Direction::Left => Direction::Up,
+                            Direction::Up => Direction::Right,
+                        };
+                    }
+                    Key::Char('t') => {
+                        self.test_play();
+                        self.draw();
+                    }
+                    Key::Ctrl('s') => self.save(),
+                    Key::Char(c) => {
+                        if let Some(&brush) = Brush::ALL.iter().find(|b| b.key() == c) {
+                            self.brush = brush;
+                        }
+                    }
+                    _ => {}
+                }
+                quitting = false;
+                self.draw_cell(x, y);
+                self.draw_cell(self.cursor.0, self.cursor.1);
+                self.draw_help();
+            }
+            self.stdout.flush().unwrap();
+        }
+    }
+
+    /// Paint the cell under the cursor with the brush.
+    fn paint(&mut self) {
+        let i = self.cursor.1 as usize * self.width as usize + self.cursor.0 as usize;
+        let c = match self.brush {
+            Brush::Wall => '#',
+            Brush::FoodZone => 'f',
+            Brush::NoFood => 'x',
+            Brush::Erase => '.',
+            Brush::Spawn => match self.facing {
+                Direction::Up => '^',
+                Direction::Down => 'v',
+                Direction::Left => '<',
+                Direction::Right => '>',
+            },
+            Brush::Portal if self.cells[i].is_ascii_digit() => return,
+            Brush::Portal => {
+                // The first portal that is missing an end gets this one.
+                let free =
+                    ('0'..='9').find(|&n| self.cells.iter().filter(|&&c| c == n).count() < 2);
+                let Some(n) = free else {
+                    self.status = "All ten portals are in use.".to_string();
+                    return;
+                };
+                n
+            }
+        };
+        self.cells[i] = c;
+        self.dirty = true;
+    }
+
+    /// The level file as it would be saved.
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for line in &self.header {
+            text += line;
+            text += "\n";
+        }
+        text += "---\n";
+        for row in self.cells.chunks(self.width as usize) {
+            text.extend(row);
+            text += "\n";
+        }
+        text
+    }
+
+    /// Write the level file, warning if snake would not load it yet.
+    fn save(&mut self) {
+        let text = self.text();
+        if let Err(e) = fs::write(&self.path, &text) {
+            self.status = format!("Could not save: {e}.");
+            return;
+        }
+        self.dirty = false;
+        self.status = match Level::parse(&text) {
+            Ok(_) => format!("Saved {}.", self.path),
+            Err(e) => format!("Saved, but it cannot be played yet: {e}."),
+        };
+    }
+
+    /// Play the level as it is right now, then come back to the editor.
+    fn test_play(&mut self) {
+        let level = match Level::parse(&self.text()) {
+            Ok(level) => level,
+            Err(e) => {
+                self.status = format!("It cannot be played yet: {e}.");
+                return;
+            }
+        };
+
+        let mut game = Game::new(
+            self.board_width,
+            self.board_height,
+            vec![level],
+            &mut self.stdin,
+            &mut self.stdout,
+        );
+        game.reset();
+        game.start();
+    }
+
+    /// Where the top-left corner of the map sits on the board, the same as in a game.
+    fn offset(&self) -> (u16, u16) {
+        (
+            (self.board_width as u16 - self.width) / 2,
+            (self.board_height as u16 - self.height) / 2,
+        )
+    }
+
+    /// Draw the whole screen from scratch.
+    fn draw(&mut self) {
+        let (width, height) = (self.board_width as u16, self.board_height as u16);
+        write!(
+            self.stdout,
+            "{}{}{}",
+            clear::All,
+            style::Reset,
+            color::Fg(color::Red)
+        )
+        .unwrap();
+        for y in 0..height {
+            for x in 0..width {
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    write!(self.stdout, "{}{}", cursor::Goto(x + 1, y + 1), BORDER).unwrap();
+                }
+            }
+        }
+        write!(
+            self.stdout,
+            "{}{} Editing {} {}[t] test  [^S] save  [q] quit ",
+            cursor::Goto(3, height),
+            color::Fg(color::Reset),
+            self.path,
+            cursor::Goto(3, 1)
+        )
+        .unwrap();
+
+        let (left, top) = self.offset();
+        write!(self.stdout, "{}", color::Fg(color::AnsiValue(236))).unwrap();
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let on_map =
+                    (left..left + self.width).contains(&x) && (top..top + self.height).contains(&y);
+                if !on_map {
+                    write!(self.stdout, "{}{}", cursor::Goto(x + 1, y + 1), OFF_MAP).unwrap();
+                }
+            }
+        }
+        write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.draw_cell(x, y);
+            }
+        }
+        self.draw_help();
+        self.stdout.flush().unwrap();
+    }
+
+    /// Draw a cell of the map, inverted if the cursor is on it.
+    fn draw_cell(&mut self, x: u16, y: u16) {
+        let c = self.cells[y as usize * self.width as usize + x as usize];
+        let (color, glyph) = match c {
+            '#' => (9, BORDER.to_string()),
+            '0'..='9' => (14, c.to_string()),
+            '^' => (7, head_glyph(Direction::Up).to_string()),
+            'v' => (7, head_glyph(Direction::Down).to_string()),
+            '<' => (7, head_glyph(Direction::Left).to_string()),
+            '>' => (7, head_glyph(Direction::Right).to_string()),
+            'f' => (10, FOOD_ZONE.to_string()),
+            'x' => (240, NO_FOOD.to_string()),
+            _ => (7, " ".to_string()),
+        };
+
+        let (left, top) = self.offset();
+        write!(
+            self.stdout,
+            "{}{}{}{}{}",
+            cursor::Goto(left + x + 1, top + y + 1),
+            color::Fg(color::AnsiValue(color)),
+            if (x, y) == self.cursor {
+                style::Invert.to_string()
+            } else {
+                String::new()
+            },
+            glyph,
+            style::Reset
+        )
+        .unwrap();
+    }
+
+    /// Draw the brushes below the board, the one in use inverted, or the status if there is
+    /// one.
+    fn draw_help(&mut self) {
+        write!(
+            self.stdout,
+            "{}{}",
+            cursor::Goto(1, self.board_height as u16 + 1),
+            clear::CurrentLine
+        )
+        .unwrap();
+        if !self.status.is_empty() {
+            write!(self.stdout, "{}", self.status).unwrap();
+            return;
+        }
+
+        let mut used = 0;
+        for brush in Brush::ALL {
+            let mut text = format!("{} {}", brush.key(), brush.name());
+            if brush == Brush::Spawn {
+                text = format!("{text} {}", head_glyph(self.facing));
+            }
+            let width = text.chars().count() + 2;
+            if used + width > self.board_width {
+                break;
+            }
+            used += width;
+            if brush == self.brush {
+                write!(self.stdout, "{}{text}{}  ", style::Invert, style::Reset).unwrap();
+            } else {
+                write!(self.stdout, "{text}  ").unwrap();
+            }
+        }
+    }
+}
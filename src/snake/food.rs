@@ -0,0 +1,190 @@
+//! The kinds of food a snake can eat and how often each one shows up.
+
+use crate::graphics::{COIN, GOLDEN_FOOD, POISON_FOOD, SLOW_FOOD};
+use std::{fmt, time::Duration};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum FoodKind {
+    /// Worth a point and speeds the game up.
+    Normal,
+    /// Worth more, but only lies around for a few seconds.
+    Golden,
+    /// Shrinks the snake instead of growing it, and goes off after a while.
+    Poison,
+    /// Worth a point and slows the game down again.
+    Slow,
+}
+
+impl FoodKind {
+    pub const ALL: [FoodKind; 4] = [
+        FoodKind::Normal,
+        FoodKind::Golden,
+        FoodKind::Poison,
+        FoodKind::Slow,
+    ];
+
+    pub fn glyph(self) -> &'static str {
+        match self {
+            FoodKind::Normal => COIN,
+            FoodKind::Golden => GOLDEN_FOOD,
+            FoodKind::Poison => POISON_FOOD,
+            FoodKind::Slow => SLOW_FOOD,
+        }
+    }
+
+    /// The ANSI colour the food is drawn in.
+    pub fn color(self) -> u8 {
+        match self {
+            FoodKind::Normal => 7,
+            FoodKind::Golden => 11,
+            FoodKind::Poison => 10,
+            FoodKind::Slow => 12,
+        }
+    }
+
+    /// The name used for the food in a spawn table.
+    fn name(self) -> &'static str {
+        match self {
+            FoodKind::Normal => "normal",
+            FoodKind::Golden => "golden",
+            FoodKind::Poison => "poison",
+            FoodKind::Slow => "slow",
+        }
+    }
+
+    /// The points eating it is worth.
+    pub fn points(self) -> i32 {
+        match self {
+            FoodKind::Normal | FoodKind::Slow => 1,
+            FoodKind::Golden => 5,
+            FoodKind::Poison => 0,
+        }
+    }
+
+    /// How long the food stays on the board before it moves elsewhere, if it ever does.
+    pub fn lifetime(self) -> Option<Duration> {
+        match self {
+            FoodKind::Normal | FoodKind::Slow => None,
+            FoodKind::Golden => Some(Duration::from_secs(5)),
+            FoodKind::Poison => Some(Duration::from_secs(8)),
+        }
+    }
+}
+
+/// How likely each kind of food is to be the next one, as weights in `FoodKind::ALL` order.
+#[derive(Clone, Copy)]
+pub struct SpawnTable {
+    weights: [u32; 4],
+}
+
+impl Default for SpawnTable {
+    fn default() -> SpawnTable {
+        SpawnTable {
+            weights: [12, 2, 2, 1],
+        }
+    }
+}
+
+/// Writes the table the way `SpawnTable::parse` reads it, with every kind spelled out.
+impl fmt::Display for SpawnTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries: Vec<String> = FoodKind::ALL
+            .iter()
+            .zip(&self.weights)
+            .map(|(kind, weight)| format!("{}={weight}", kind.name()))
+            .collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+impl SpawnTable {
+    /// Parse a table like `golden=3,poison=0`. Kinds that are left out keep their default
+    /// weight.
+    pub fn parse(src: &str) -> Result<SpawnTable, String> {
+        let mut table = SpawnTable::default();
+
+        for entry in src.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (name, weight) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected kind=weight, got {entry}"))?;
+            let i = FoodKind::ALL
+                .iter()
+                .position(|kind| kind.name() == name.trim())
+                .ok_or_else(|| format!("unknown food: {}", name.trim()))?;
+            table.weights[i] = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight: {}", weight.trim()))?;
+        }
+
+        if table.weights.iter().all(|&w| w == 0) {
+            return Err("every food has a weight of 0".to_string());
+        }
+        Ok(table)
+    }
+
+    /// Pick a kind of food from a random `roll`.
+    pub fn pick(&self, roll: u32) -> FoodKind {
+        let mut roll = roll % self.weights.iter().sum::<u32>();
+        for (kind, &weight) in FoodKind::ALL.iter().zip(&self.weights) {
+            if roll < weight {
+                return *kind;
+            }
+            roll -= weight;
+        }
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_partial_table() {
+        let table = SpawnTable::parse(" golden = 3 ,poison=0,").unwrap();
+        assert_eq!(table.weights, [12, 3, 0, 1]);
+        assert_eq!(SpawnTable::parse("").unwrap().weights, [12, 2, 2, 1]);
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let table = SpawnTable::parse("normal=1,slow=9").unwrap();
+        let text = table.to_string();
+        assert_eq!(text, "normal=1,golden=2,poison=2,slow=9");
+        assert_eq!(SpawnTable::parse(&text).unwrap().weights, table.weights);
+    }
+
+    #[test]
+    fn rejects_broken_tables() {
+        let err = SpawnTable::parse("golden").err().unwrap();
+        assert!(err.contains("expected kind=weight"), "{err}");
+        let err = SpawnTable::parse("cake=1").err().unwrap();
+        assert!(err.contains("unknown food"), "{err}");
+        let err = SpawnTable::parse("golden=-1").err().unwrap();
+        assert!(err.contains("invalid weight"), "{err}");
+        let err = SpawnTable::parse("normal=0,golden=0,poison=0,slow=0")
+            .err()
+            .unwrap();
+        assert!(err.contains("weight of 0"), "{err}");
+    }
+
+    #[test]
+    fn picks_by_weight() {
+        let table = SpawnTable::parse("normal=2,golden=0,poison=1,slow=1").unwrap();
+        let picks: Vec<FoodKind> = (0..8).map(|roll| table.pick(roll)).collect();
+        assert!(
+            picks
+                == [
+                    FoodKind::Normal,
+                    FoodKind::Normal,
+                    FoodKind::Poison,
+                    FoodKind::Slow,
+                    FoodKind::Normal,
+                    FoodKind::Normal,
+                    FoodKind::Poison,
+                    FoodKind::Slow,
+                ]
+        );
+    }
+}
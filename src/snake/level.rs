@@ -0,0 +1,397 @@
+//! Snake levels and the text format they are stored in.
+//!
+//! A level file starts with `key: value` header lines, followed by a line holding only
+//! `---` and then the map, one row per line:
+//!
+//! - `#` is a wall,
+//! - `x` is a cell food never spawns on,
+//! - `f` is part of a food zone: if a map has any, food only spawns on them,
+//! - a digit is one end of a portal, and a snake moving into it comes out of the other end
+//!   with the same digit,
+//! - `^`, `v`, `<` and `>` mark a snake's head and the direction it starts moving in,
+//! - anything else is an empty cell.
+//!
+//! Spawn points are handed out in reading order, player one first. A snake without its own
+//! spawn point starts mirrored through the centre of the map from player one, or on the
+//! first free stretch of a row if that spot is taken. A map only takes as many snakes as
+//! there is room for, and the spawn points of a map must not overlap.
+//!
+//! The known header keys are `name` and `target`, the score that unlocks the next level.
+//! The map is drawn centred on the board, inside the board's own walls, and whatever room
+//! is left around it is walled off.
+
+use super::{Direction, START_LENGTH};
+use std::iter;
+
+/// The built-in campaign, played in order.
+const CAMPAIGN: [&str; 4] = [
+    include_str!("levels/01-open.txt"),
+    include_str!("levels/02-pillars.txt"),
+    include_str!("levels/03-cross.txt"),
+    include_str!("levels/04-rooms.txt"),
+];
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Tile {
+    Empty,
+    Wall,
+    NoFood,
+    FoodZone,
+    /// One end of the portal with this number.
+    Portal(u8),
+}
+
+impl Tile {
+    /// Read a tile from its character in a map. Spawn points are empty cells.
+    pub fn from_code(c: char) -> Tile {
+        match c {
+            '#' => Tile::Wall,
+            'x' => Tile::NoFood,
+            'f' => Tile::FoodZone,
+            '0'..='9' => Tile::Portal(c as u8 - b'0'),
+            _ => Tile::Empty,
+        }
+    }
+
+    /// The character the tile is written as in a map.
+    pub fn code(self) -> char {
+        match self {
+            Tile::Empty => '.',
+            Tile::Wall => '#',
+            Tile::NoFood => 'x',
+            Tile::FoodZone => 'f',
+            Tile::Portal(n) => (b'0' + n) as char,
+        }
+    }
+
+    /// Whether a snake can lie on the tile.
+    pub fn is_open(self) -> bool {
+        !matches!(self, Tile::Wall | Tile::Portal(_))
+    }
+}
+
+#[derive(Clone)]
+pub struct Level {
+    pub name: String,
+    /// The score that unlocks the next level.
+    pub target: Option<i32>,
+    pub width: u16,
+    pub height: u16,
+    tiles: Vec<Tile>,
+    /// Where each snake's head starts, in map coordinates, and the direction it starts
+    /// moving in.
+    spawns: Vec<((u16, u16), Direction)>,
+}
+
+impl Level {
+    /// Load the built-in campaign.
+    pub fn campaign() -> Vec<Level> {
+        CAMPAIGN
+            .iter()
+            .map(|src| Level::parse(src).unwrap())
+            .collect()
+    }
+
+    /// A level with nothing but a name, for a board laid out elsewhere, like by the host of
+    /// a network match.
+    pub fn blank(name: &str) -> Level {
+        Level {
+            name: name.to_string(),
+            target: None,
+            width: 0,
+            height: 0,
+            tiles: Vec::new(),
+            spawns: Vec::new(),
+        }
+    }
+
+    /// Parse a level file.
+    pub fn parse(src: &str) -> Result<Level, String> {
+        let mut lines = src.lines();
+        let mut name = String::new();
+        let mut target = None;
+
+        for line in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            match key.trim() {
+                "name" => name = value.trim().to_string(),
+                "target" => {
+                    target = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("invalid target: {}", value.trim()))?,
+                    );
+                }
+                key => return Err(format!("unknown key: {key}")),
+            }
+        }
+
+        let rows: Vec<Vec<char>> = lines.map(|line| line.chars().collect()).collect();
+        let height = u16::try_from(rows.len()).map_err(|_| "the map is too tall")?;
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let width = u16::try_from(width).map_err(|_| "the map is too wide")?;
+
+        let mut tiles = vec![Tile::Empty; width as usize * height as usize];
+        let mut spawns = Vec::new();
+
+        for (y, row) in (0..).zip(&rows) {
+            for (x, &c) in (0..).zip(row) {
+                tiles[y as usize * width as usize + x as usize] = Tile::from_code(c);
+                match c {
+                    '^' | 'v' | '<' | '>' => {
+                        let direction = match c {
+                            '^' => Direction::Up,
+                            'v' => Direction::Down,
+                            '<' => Direction::Left,
+                            _ => Direction::Right,
+                        };
+                        spawns.push(((x, y), direction));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if spawns.is_empty() {
+            return Err("the map has no spawn point".to_string());
+        }
+        for n in 0..10 {
+            let ends = tiles.iter().filter(|&&t| t == Tile::Portal(n)).count();
+            if ends != 0 && ends != 2 {
+                return Err(format!("portal {n} needs exactly two ends, not {ends}"));
+            }
+        }
+
+        let level = Level {
+            name,
+            target,
+            width,
+            height,
+            tiles,
+            spawns,
+        };
+
+        let mut taken = Vec::new();
+        for &spawn in &level.spawns {
+            let body = body(spawn);
+            if !level.fits(&body, &[]) {
+                return Err(format!(
+                    "a snake needs {START_LENGTH} free cells behind its spawn point"
+                ));
+            }
+            if !level.fits(&body, &taken) {
+                return Err("two snakes would start on top of each other".to_string());
+            }
+            taken.extend(body);
+        }
+
+        Ok(level)
+    }
+
+    /// Where the `i`th snake starts, as its body in map coordinates from the tail to the head
+    /// and the direction it starts moving in. Snakes without a spawn point of their own avoid
+    /// the cells in `taken`. Returns `None` if there is no room left for it.
+    pub fn place(&self, i: usize, taken: &[(i32, i32)]) -> Option<(Vec<(i32, i32)>, Direction)> {
+        if let Some(&spawn) = self.spawns.get(i) {
+            return Some((body(spawn), spawn.1));
+        }
+
+        let ((x, y), direction) = *self.spawns.first()?;
+        let mirrored = (
+            (self.width - 1 - x, self.height - 1 - y),
+            direction.opposite(),
+        );
+
+        // Rows from the middle of the map outwards.
+        let middle = self.height / 2;
+        let rows = (0..self.height).map(move |k| {
+            if k % 2 == 0 {
+                middle + k / 2
+            } else {
+                middle - k.div_ceil(2)
+            }
+        });
+        let stretches = rows.flat_map(|y| {
+            (START_LENGTH as u16 - 1..self.width).map(move |x| ((x, y), Direction::Right))
+        });
+
+        iter::once(mirrored)
+            .chain(stretches)
+            .map(|spawn| (body(spawn), spawn.1))
+            .find(|(body, _)| self.fits(body, taken))
+    }
+
+    /// How many snakes, up to `max`, can be placed on the map one after the other.
+    pub fn room(&self, max: usize) -> usize {
+        let mut taken = Vec::new();
+        (0..max)
+            .take_while(|&i| match self.place(i, &taken) {
+                Some((body, _)) => {
+                    taken.extend(body);
+                    true
+                }
+                None => false,
+            })
+            .count()
+    }
+
+    /// Whether the map has neither walls nor portals on it.
+    pub fn is_open(&self) -> bool {
+        self.tiles.iter().all(|tile| tile.is_open())
+    }
+
+    /// Whether a body lies within the map, off the walls and portals and clear of `taken`.
+    fn fits(&self, body: &[(i32, i32)], taken: &[(i32, i32)]) -> bool {
+        body.iter()
+            .all(|&(x, y)| self.tile(x, y).is_some_and(Tile::is_open) && !taken.contains(&(x, y)))
+    }
+
+    /// The tile at a map position, or `None` outside the map.
+    pub fn tile(&self, x: i32, y: i32) -> Option<Tile> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(self.tiles[y as usize * self.width as usize + x as usize])
+    }
+}
+
+/// The starting body for a spawn point in map coordinates, from the tail to the head.
+fn body(((x, y), direction): ((u16, u16), Direction)) -> Vec<(i32, i32)> {
+    let (dx, dy) = match direction {
+        Direction::Up => (0, -1),
+        Direction::Down => (0, 1),
+        Direction::Left => (-1, 0),
+        Direction::Right => (1, 0),
+    };
+    let (x, y) = (x as i32, y as i32);
+
+    (0..START_LENGTH as i32)
+        .rev()
+        .map(|i| (x - dx * i, y - dy * i))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A level file with the given map under a short header.
+    fn parse(map: &[&str]) -> Result<Level, String> {
+        Level::parse(&format!("name: Test\ntarget: 7\n---\n{}", map.join("\n")))
+    }
+
+    /// An open map `width` cells wide with a single row holding `row`.
+    fn with_row(width: usize, row: &str) -> Vec<String> {
+        let mut map = vec![".".repeat(width); 3];
+        map[1] = format!("{row:.<width$}");
+        map
+    }
+
+    #[test]
+    fn header_and_tiles() {
+        let level = parse(&["#xf1", "..........>", "1"]).unwrap();
+        assert_eq!(level.name, "Test");
+        assert_eq!(level.target, Some(7));
+        assert_eq!((level.width, level.height), (11, 3));
+
+        assert!(level.tile(0, 0) == Some(Tile::Wall));
+        assert!(level.tile(1, 0) == Some(Tile::NoFood));
+        assert!(level.tile(2, 0) == Some(Tile::FoodZone));
+        assert!(level.tile(3, 0) == Some(Tile::Portal(1)));
+        assert!(level.tile(10, 1) == Some(Tile::Empty));
+        // Short rows are padded with empty cells.
+        assert!(level.tile(10, 2) == Some(Tile::Empty));
+        assert!(level.tile(11, 0).is_none());
+        assert!(level.tile(-1, 0).is_none());
+    }
+
+    #[test]
+    fn the_campaign_parses() {
+        assert_eq!(Level::campaign().len(), CAMPAIGN.len());
+    }
+
+    #[test]
+    fn rejects_broken_headers() {
+        let err = Level::parse("colour: red\n---\n..........>").err().unwrap();
+        assert!(err.contains("unknown key"), "{err}");
+        let err = Level::parse("target: lots\n---\n..........>")
+            .err()
+            .unwrap();
+        assert!(err.contains("invalid target"), "{err}");
+    }
+
+    #[test]
+    fn rejects_broken_maps() {
+        let err = parse(&["....", "...."]).err().unwrap();
+        assert!(err.contains("no spawn point"), "{err}");
+
+        let err = parse(&["..........>", "1.2.2"]).err().unwrap();
+        assert!(err.contains("portal 1"), "{err}");
+        let err = parse(&["..........>", "3.3.3"]).err().unwrap();
+        assert!(err.contains("portal 3"), "{err}");
+
+        // The body would stick out of the map, or lie on a wall or a portal.
+        let err = parse(&["........>"]).err().unwrap();
+        assert!(err.contains("free cells"), "{err}");
+        let err = parse(&["....#.....>"]).err().unwrap();
+        assert!(err.contains("free cells"), "{err}");
+        let err = parse(&["....1.....>1"]).err().unwrap();
+        assert!(err.contains("free cells"), "{err}");
+    }
+
+    #[test]
+    fn rejects_overlapping_spawns() {
+        let mut map = vec![".".repeat(12); 11];
+        map[0] = ".....^......".to_string();
+        map[1] = "..........>.".to_string();
+        let map: Vec<&str> = map.iter().map(String::as_str).collect();
+        let err = parse(&map).err().unwrap();
+        assert!(err.contains("on top of each other"), "{err}");
+    }
+
+    #[test]
+    fn places_snakes_on_their_spawns() {
+        let level = parse(&["..........>", "<.........."]).unwrap();
+
+        let (body, direction) = level.place(0, &[]).unwrap();
+        assert!(direction == Direction::Right);
+        assert_eq!(body.len(), START_LENGTH);
+        assert_eq!(body.first(), Some(&(1, 0)));
+        assert_eq!(body.last(), Some(&(10, 0)));
+
+        let (body, direction) = level.place(1, &[]).unwrap();
+        assert!(direction == Direction::Left);
+        assert_eq!(body.first(), Some(&(9, 1)));
+        assert_eq!(body.last(), Some(&(0, 1)));
+    }
+
+    #[test]
+    fn places_extra_snakes_clear_of_the_others() {
+        let map = with_row(24, "..........>");
+        let map: Vec<&str> = map.iter().map(String::as_str).collect();
+        let level = parse(&map).unwrap();
+
+        let mut taken = Vec::new();
+        for i in 0..3 {
+            let (body, _) = level.place(i, &taken).unwrap();
+            assert_eq!(body.len(), START_LENGTH);
+            assert!(body.iter().all(|cell| !taken.contains(cell)));
+            assert!(level.fits(&body, &[]));
+            taken.extend(body);
+        }
+        assert_eq!(level.room(4), 4);
+    }
+
+    #[test]
+    fn counts_the_room_on_a_cramped_map() {
+        let level = parse(&["#..#..#..#..", "..........>."]).unwrap();
+        assert!(level.place(1, &level.place(0, &[]).unwrap().0).is_none());
+        assert_eq!(level.room(4), 1);
+    }
+}
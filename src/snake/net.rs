@@ -0,0 +1,485 @@
+//! Playing snake over TCP.
+//!
+//! One instance hosts the match and runs the game, the others join it and only send their
+//! turns. Every message is a single line of text, starting with its kind:
+//!
+//! - `WELCOME <player>` tells a client which snake is theirs, counting from 0.
+//! - `BOARD <width> <height> <walls> <level> <tiles> <name>` describes the board whenever a
+//!   level starts. `tiles` has one character per cell, row by row, written the same way as
+//!   in a level file. The level name is the rest of the line.
+//! - `FRAME <players> <speed> <seconds> <food> <snake>...` is the state after every move.
+//!   The food is `x,y,kind`, or `-` when there is none, and each snake is `alive:score:body`,
+//!   where the body lists `x,y,direction` parts from the tail to the head, separated by `;`.
+//! - `END <message>` is shown when a round or the game is over.
+//! - `TURN <direction>` is sent by a client to steer its snake.
+//! - `QUIT` is sent by a client leaving the game.
+//!
+//! Directions are `U`, `D`, `L` and `R`, walls are `solid` or `portal`.
+
+use super::{food::FoodKind, level::Tile, Direction, Walls, MIN_HEIGHT, MIN_WIDTH};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+/// How long sending a message may take before the other end counts as gone, so a peer that
+/// stops reading cannot hold up the game.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub enum Message {
+    Welcome {
+        player: usize,
+    },
+    Board {
+        width: u16,
+        height: u16,
+        walls: Walls,
+        level: usize,
+        tiles: Vec<Tile>,
+        name: String,
+    },
+    Frame(Frame),
+    End(String),
+    Turn(Direction),
+    Quit,
+}
+
+/// Everything a client needs to draw the board after a move.
+pub struct Frame {
+    /// The number of snakes steered by people, the rest are AI snakes.
+    pub players: usize,
+    pub speed: u64,
+    /// Seconds spent on the level.
+    pub elapsed: u64,
+    /// There is no food on a board of light cycles.
+    pub food: Option<(u16, u16, FoodKind)>,
+    pub snakes: Vec<SnakeState>,
+}
+
+pub struct SnakeState {
+    pub alive: bool,
+    pub score: i32,
+    /// Body parts from the tail to the head, with the direction each one moved in.
+    pub body: Vec<(u16, u16, Direction)>,
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Welcome { player } => format!("WELCOME {player}"),
+            Message::Board {
+                width,
+                height,
+                walls,
+                level,
+                tiles,
+                name,
+            } => {
+                let tiles: String = tiles.iter().map(|tile| tile.code()).collect();
+                format!(
+                    "BOARD {width} {height} {} {level} {tiles} {name}",
+                    walls.name()
+                )
+            }
+            Message::Frame(frame) => {
+                let food = match frame.food {
+                    Some((x, y, kind)) => {
+                        let kind = FoodKind::ALL.iter().position(|&k| k == kind).unwrap();
+                        format!("{x},{y},{kind}")
+                    }
+                    None => "-".to_string(),
+                };
+                let mut line = format!(
+                    "FRAME {} {} {} {food}",
+                    frame.players, frame.speed, frame.elapsed
+                );
+                for snake in &frame.snakes {
+                    let body: Vec<String> = snake
+                        .body
+                        .iter()
+                        .map(|&(x, y, d)| format!("{x},{y},{}", d.code()))
+                        .collect();
+                    line += &format!(
+                        " {}:{}:{}",
+                        u8::from(snake.alive),
+                        snake.score,
+                        body.join(";")
+                    );
+                }
+                line
+            }
+            Message::End(message) => format!("END {message}"),
+            Message::Turn(direction) => format!("TURN {}", direction.code()),
+            Message::Quit => "QUIT".to_string(),
+        }
+    }
+
+    /// Decode a line. Positions in a frame are checked against `board`, the size of the
+    /// board described last, and a frame before any board is turned down.
+    pub fn decode(line: &str, board: Option<(u16, u16)>) -> Result<Message, String> {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let mut fields = rest.split(' ');
+        let mut field = || fields.next().ok_or_else(|| format!("{kind} is too short"));
+
+        Ok(match kind {
+            "WELCOME" => Message::Welcome {
+                player: number(field()?)?,
+            },
+            "BOARD" => {
+                let width: u16 = number(field()?)?;
+                let height: u16 = number(field()?)?;
+                if usize::from(width) < MIN_WIDTH || usize::from(height) < MIN_HEIGHT {
+                    return Err(format!("the board is too small: {width}x{height}"));
+                }
+                let walls = match field()? {
+                    "solid" => Walls::Solid,
+                    "portal" => Walls::Portal,
+                    walls => return Err(format!("unknown walls: {walls}")),
+                };
+                let level = number(field()?)?;
+                let tiles = field()?.chars().map(Tile::from_code).collect::<Vec<_>>();
+                if tiles.len() != width as usize * height as usize {
+                    return Err("the tiles do not match the board size".to_string());
+                }
+                let name = fields.collect::<Vec<_>>().join(" ");
+                Message::Board {
+                    width,
+                    height,
+                    walls,
+                    level,
+                    tiles,
+                    name,
+                }
+            }
+            "FRAME" => {
+                let (width, height) = board.ok_or("a frame before the board")?;
+                let on_board = |x: u16, y: u16| {
+                    if x < width && y < height {
+                        Ok((x, y))
+                    } else {
+                        Err(format!("{x},{y} is off the board"))
+                    }
+                };
+                let players = number(field()?)?;
+                let speed = number(field()?)?;
+                let elapsed = number(field()?)?;
+                let food = match field()? {
+                    "-" => None,
+                    food => {
+                        let [x, y, kind] = food.split(',').collect::<Vec<_>>()[..] else {
+                            return Err("invalid food".to_string());
+                        };
+                        let kind = *FoodKind::ALL
+                            .get(number::<usize>(kind)?)
+                            .ok_or("unknown food")?;
+                        let (x, y) = on_board(number(x)?, number(y)?)?;
+                        Some((x, y, kind))
+                    }
+                };
+                let snakes = fields
+                    .map(|field| snake(field, on_board))
+                    .collect::<Result<_, _>>()?;
+                Message::Frame(Frame {
+                    players,
+                    speed,
+                    elapsed,
+                    food,
+                    snakes,
+                })
+            }
+            "END" => Message::End(rest.to_string()),
+            "TURN" => Message::Turn(direction(field()?)?),
+            "QUIT" => Message::Quit,
+            kind => return Err(format!("unknown message: {kind}")),
+        })
+    }
+}
+
+fn number<T: std::str::FromStr>(field: &str) -> Result<T, String> {
+    field
+        .parse()
+        .map_err(|_| format!("invalid number: {field}"))
+}
+
+fn direction(field: &str) -> Result<Direction, String> {
+    Direction::from_code(field).ok_or_else(|| format!("invalid direction: {field}"))
+}
+
+fn snake(
+    field: &str,
+    on_board: impl Fn(u16, u16) -> Result<(u16, u16), String>,
+) -> Result<SnakeState, String> {
+    let mut parts = field.splitn(3, ':');
+    let (Some(alive), Some(score), Some(body)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(format!("invalid snake: {field}"));
+    };
+
+    let body = body
+        .split(';')
+        .map(|part| match part.split(',').collect::<Vec<_>>()[..] {
+            [x, y, d] => {
+                let (x, y) = on_board(number(x)?, number(y)?)?;
+                Ok((x, y, direction(d)?))
+            }
+            _ => Err(format!("invalid body part: {part}")),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if body.is_empty() {
+        return Err("a snake without a body".to_string());
+    }
+
+    Ok(SnakeState {
+        alive: alive == "1",
+        score: number(score)?,
+        body,
+    })
+}
+
+/// The other end of a connection.
+///
+/// Incoming lines are read and decoded on a thread of their own, so checking for messages
+/// never blocks the game loop.
+pub struct Peer {
+    stream: TcpStream,
+    inbox: Receiver<Message>,
+}
+
+impl Peer {
+    pub fn new(stream: TcpStream) -> io::Result<Peer> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (sender, inbox) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut board = None;
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                // Lines that do not make sense are skipped rather than ending the game.
+                if let Ok(message) = Message::decode(&line, board) {
+                    if let Message::Board { width, height, .. } = message {
+                        board = Some((width, height));
+                    }
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Peer { stream, inbox })
+    }
+
+    /// Send a message. Returns false if the connection is gone, or too slow to take it.
+    pub fn send(&mut self, message: &Message) -> bool {
+        let line = message.encode() + "\n";
+        self.stream.write_all(line.as_bytes()).is_ok()
+    }
+
+    /// The messages that came in since the last call, or `None` once the connection is
+    /// closed and everything sent before that has been handed out.
+    pub fn receive(&mut self) -> Option<Vec<Message>> {
+        let mut messages = Vec::new();
+        loop {
+            match self.inbox.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(TryRecvError::Empty) => return Some(messages),
+                Err(TryRecvError::Disconnected) if messages.is_empty() => return None,
+                Err(TryRecvError::Disconnected) => return Some(messages),
+            }
+        }
+    }
+}
+
+/// The hosting side of a match: the port clients join on and the clients that did.
+pub struct Host {
+    listener: TcpListener,
+    pub port: u16,
+    /// Every client along with the number of the player it steers.
+    pub clients: Vec<(usize, Peer)>,
+}
+
+impl Host {
+    pub fn bind(port: u16) -> io::Result<Host> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Host {
+            port: listener.local_addr()?.port(),
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// A client that is waiting to join, if there is one.
+    pub fn accept(&mut self) -> Option<Peer> {
+        let (stream, _) = self.listener.accept().ok()?;
+        Peer::new(stream).ok()
+    }
+
+    /// Send a message to every client, dropping the ones that are gone.
+    pub fn broadcast(&mut self, message: &Message) {
+        self.clients.retain_mut(|(_, peer)| peer.send(message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The size of the board the messages in the tests are decoded against.
+    const BOARD: Option<(u16, u16)> = Some((MIN_WIDTH as u16, MIN_HEIGHT as u16));
+
+    /// Encode a message and decode it again.
+    fn round_trip(message: &Message) -> Message {
+        let line = message.encode();
+        assert!(!line.contains('\n'), "{line}");
+        Message::decode(&line, BOARD).unwrap()
+    }
+
+    #[test]
+    fn welcome() {
+        let Message::Welcome { player } = round_trip(&Message::Welcome { player: 2 }) else {
+            panic!("not a welcome");
+        };
+        assert_eq!(player, 2);
+    }
+
+    #[test]
+    fn board() {
+        let (width, height) = (MIN_WIDTH as u16, MIN_HEIGHT as u16);
+        let mut tiles = vec![Tile::Empty; MIN_WIDTH * MIN_HEIGHT];
+        tiles[0] = Tile::Wall;
+        tiles[1] = Tile::NoFood;
+        tiles[2] = Tile::FoodZone;
+        tiles[3] = Tile::Portal(7);
+
+        let Message::Board {
+            width: w,
+            height: h,
+            walls,
+            level,
+            tiles: t,
+            name,
+        } = round_trip(&Message::Board {
+            width,
+            height,
+            walls: Walls::Portal,
+            level: 3,
+            tiles: tiles.clone(),
+            name: "The long way round".to_string(),
+        })
+        else {
+            panic!("not a board");
+        };
+        assert_eq!((w, h, level), (width, height, 3));
+        assert!(walls == Walls::Portal);
+        assert!(t == tiles);
+        assert_eq!(name, "The long way round");
+    }
+
+    #[test]
+    fn frame() {
+        let message = Message::Frame(Frame {
+            players: 2,
+            speed: 12,
+            elapsed: 34,
+            food: Some((5, 6, FoodKind::Golden)),
+            snakes: vec![
+                SnakeState {
+                    alive: true,
+                    score: -3,
+                    body: vec![(1, 2, Direction::Right), (2, 2, Direction::Up)],
+                },
+                SnakeState {
+                    alive: false,
+                    score: 7,
+                    body: vec![(9, 9, Direction::Left)],
+                },
+            ],
+        });
+
+        let Message::Frame(frame) = round_trip(&message) else {
+            panic!("not a frame");
+        };
+        assert_eq!((frame.players, frame.speed, frame.elapsed), (2, 12, 34));
+        assert!(frame.food == Some((5, 6, FoodKind::Golden)));
+        assert_eq!(frame.snakes.len(), 2);
+        assert!(frame.snakes[0].alive && !frame.snakes[1].alive);
+        assert_eq!((frame.snakes[0].score, frame.snakes[1].score), (-3, 7));
+        assert!(frame.snakes[0].body == [(1, 2, Direction::Right), (2, 2, Direction::Up)]);
+        assert!(frame.snakes[1].body == [(9, 9, Direction::Left)]);
+        assert_eq!(
+            round_trip(&Message::Frame(frame)).encode(),
+            message.encode()
+        );
+    }
+
+    #[test]
+    fn frame_without_food() {
+        let Message::Frame(frame) = round_trip(&Message::Frame(Frame {
+            players: 1,
+            speed: 8,
+            elapsed: 0,
+            food: None,
+            snakes: Vec::new(),
+        })) else {
+            panic!("not a frame");
+        };
+        assert!(frame.food.is_none());
+        assert!(frame.snakes.is_empty());
+    }
+
+    #[test]
+    fn end_turn_and_quit() {
+        let Message::End(text) = round_trip(&Message::End("Player 1 wins!".to_string())) else {
+            panic!("not an end");
+        };
+        assert_eq!(text, "Player 1 wins!");
+
+        let Message::Turn(direction) = round_trip(&Message::Turn(Direction::Down)) else {
+            panic!("not a turn");
+        };
+        assert!(direction == Direction::Down);
+
+        assert!(matches!(round_trip(&Message::Quit), Message::Quit));
+    }
+
+    #[test]
+    fn rejects_broken_lines() {
+        let tiles = ".".repeat(MIN_WIDTH * MIN_HEIGHT);
+        let board =
+            |width, height, tiles: &str| format!("BOARD {width} {height} solid 0 {tiles} x");
+        assert!(Message::decode(&board(MIN_WIDTH, MIN_HEIGHT, &tiles), None).is_ok());
+        assert!(Message::decode(&board(MIN_WIDTH, MIN_HEIGHT, "."), None).is_err());
+        assert!(Message::decode(&board(1, 1, "."), None).is_err());
+        assert!(Message::decode(&board(0, 0, ""), None).is_err());
+
+        assert!(Message::decode("WELCOME", None).is_err());
+        assert!(Message::decode("TURN X", None).is_err());
+        assert!(Message::decode("FRAME 1 8 0 - 1:0:", BOARD).is_err());
+        assert!(Message::decode("HELLO", None).is_err());
+    }
+
+    #[test]
+    fn rejects_frames_off_the_board() {
+        let (width, height) = (MIN_WIDTH, MIN_HEIGHT);
+        assert!(Message::decode("FRAME 1 8 0 1,1,0 1:0:2,2,R", BOARD).is_ok());
+        assert!(Message::decode("FRAME 1 8 0 1,1,0 1:0:2,2,R", None).is_err());
+
+        let food = format!("FRAME 1 8 0 {width},1,0 1:0:2,2,R");
+        assert!(Message::decode(&food, BOARD).is_err());
+        let food = format!("FRAME 1 8 0 1,{height},0 1:0:2,2,R");
+        assert!(Message::decode(&food, BOARD).is_err());
+
+        let body = format!("FRAME 1 8 0 - 1:0:2,2,R;{width},2,R");
+        assert!(Message::decode(&body, BOARD).is_err());
+        let body = format!("FRAME 1 8 0 - 1:0:2,{height},D");
+        assert!(Message::decode(&body, BOARD).is_err());
+    }
+}
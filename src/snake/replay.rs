@@ -0,0 +1,319 @@
+//! Recording the best snake runs and racing their ghosts.
+//!
+//! Snake only takes random numbers from a seed picked when a run starts, and food expires
+//! after a number of moves rather than seconds, so a run plays out the same way every time
+//! given its seed and the turns the player took on each move. That is all a recording keeps.
+//!
+//! The best run for each combination of settings is saved in `snake-ghosts.txt`, one per
+//! line: the settings, the seed, the score and the turns, separated by tabs. Every turn is
+//! the move it was taken on followed by its direction, like `12U`.
+
+use super::{body_glyph, head_glyph, Direction, Game, Step, GHOST_COLOR, SNAKE_TAIL};
+use crate::{graphics::color, graphics::cursor, save};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+const FILE: &str = "snake-ghosts.txt";
+
+pub struct Run {
+    /// What the run was played with, see `Game::settings`. A ghost only shows up in games
+    /// with the same settings.
+    pub settings: String,
+    pub seed: u64,
+    pub score: i32,
+    /// The turns player one took, along with the move each was taken on.
+    pub turns: Vec<(u64, Direction)>,
+}
+
+impl Run {
+    fn parse(line: &str) -> Result<Run, String> {
+        let [settings, seed, score, turns] = line.split('\t').collect::<Vec<_>>()[..] else {
+            return Err("expected four fields".to_string());
+        };
+
+        let turns = turns
+            .split_whitespace()
+            .map(|turn| {
+                let (at, _) = turn.char_indices().last().unwrap();
+                let (tick, direction) = turn.split_at(at);
+                Ok((
+                    tick.parse().map_err(|_| format!("invalid move: {tick}"))?,
+                    Direction::from_code(direction)
+                        .ok_or_else(|| format!("invalid direction: {direction}"))?,
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Run {
+            settings: settings.to_string(),
+            seed: seed.parse().map_err(|_| format!("invalid seed: {seed}"))?,
+            score: score
+                .parse()
+                .map_err(|_| format!("invalid score: {score}"))?,
+            turns,
+        })
+    }
+
+    fn encode(&self) -> String {
+        let turns: Vec<String> = self
+            .turns
+            .iter()
+            .map(|&(tick, direction)| format!("{tick}{}", direction.code()))
+            .collect();
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.settings,
+            self.seed,
+            self.score,
+            turns.join(" ")
+        )
+    }
+}
+
+/// Load the saved runs, skipping any that cannot be read.
+pub fn load() -> Vec<Run> {
+    save::read(FILE)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| Run::parse(line).ok())
+        .collect()
+}
+
+/// A replay of a saved run, moving along on its own clock next to the player's game.
+pub struct Ghost {
+    game: Game<io::Empty, io::Sink>,
+    /// The turns that are still to come.
+    turns: VecDeque<(u64, Direction)>,
+    /// When the ghost makes its next move.
+    next_tick: Instant,
+    /// The cells the ghost was last drawn on.
+    drawn: Vec<(u16, u16)>,
+    /// Whether the run is over.
+    done: bool,
+}
+
+impl Ghost {
+    /// Make the moves that are due by `now`.
+    fn catch_up(&mut self, now: Instant) {
+        while !self.done && self.next_tick <= now {
+            while let Some(&(_, direction)) = self
+                .turns
+                .front()
+                .filter(|&&(tick, _)| tick == self.game.tick)
+            {
+                self.game.queue_turn(0, direction);
+                self.turns.pop_front();
+            }
+
+            // The wait comes from the speed before the move, the same as in the game loop.
+            self.next_tick += Duration::from_micros(1_000_000 / self.game.speed);
+            match self.game.step() {
+                Step::Crashed | Step::BoardFull => self.done = true,
+                Step::Moved | Step::LevelUp => {}
+            }
+        }
+    }
+
+    /// The score of the run so far.
+    pub fn score(&self) -> i32 {
+        self.game.scores[0]
+    }
+
+    /// Push the ghost's next move back by the time the game was paused.
+    pub fn delay(&mut self, paused: Duration) {
+        self.next_tick += paused;
+    }
+}
+
+impl<R: Read, W: Write> Game<R, W> {
+    /// What a run depends on besides its seed and turns: the board, the levels, the speed
+    /// settings and the food.
+    pub(super) fn settings(&self) -> String {
+        let levels: Vec<&str> = self.campaign.iter().map(|l| l.name.as_str()).collect();
+        format!(
+            "{}x{} {} {} {} {} {} {}",
+            self.width,
+            self.height,
+            self.walls.name(),
+            self.pace.curve.name(),
+            self.pace.difficulty.name(),
+            self.pace.max(),
+            self.food_table,
+            levels.join("/")
+        )
+    }
+
+    /// Whether this is a game of one player alone, the only kind that gets recorded.
+    pub(super) fn solo(&self) -> bool {
+        self.players == 1
+            && self.ai_snakes == 0
+            && !self.autopilot
+            && !self.tron
+            && self.host.is_none()
+    }
+
+    /// The best recorded run with the current settings.
+    pub(super) fn best_run(&self) -> Option<&Run> {
+        let settings = self.settings();
+        self.runs
+            .as_ref()?
+            .iter()
+            .find(|run| run.settings == settings)
+    }
+
+    /// Save the run that just ended if it beat the best one with the same settings.
+    pub(super) fn keep_best_run(&mut self) {
+        if !self.solo()
+            || self
+                .best_run()
+                .is_some_and(|run| run.score >= self.scores[0])
+        {
+            return;
+        }
+
+        let run = Run {
+            settings: self.settings(),
+            seed: self.seed,
+            score: self.scores[0],
+            turns: self.record.clone(),
+        };
+        // Saving records that were never loaded would wipe the ones in the file.
+        let Some(runs) = &mut self.runs else {
+            return;
+        };
+        runs.retain(|other| other.settings != run.settings);
+        runs.push(run);
+
+        let lines: Vec<String> = runs.iter().map(Run::encode).collect();
+        save::write_best_effort(FILE, &(lines.join("\n") + "\n"));
+    }
+
+    /// Start a replay of the best run, if ghosts are on and there is one to race.
+    pub(super) fn summon_ghost(&self) -> Option<Box<Ghost>> {
+        if !self.race_ghost || !self.solo() {
+            return None;
+        }
+        let run = self.best_run()?;
+
+        let mut game = Game::new(
+            self.width,
+            self.height,
+            self.campaign.clone(),
+            io::empty(),
+            io::sink(),
+        );
+        game.walls = self.walls;
+        game.food_table = self.food_table;
+        game.pace = self.pace;
+        game.start_run(run.seed);
+
+        Some(Box::new(Ghost {
+            game,
+            turns: run.turns.iter().copied().collect(),
+            next_tick: Instant::now(),
+            drawn: Vec::new(),
+            done: false,
+        }))
+    }
+
+    /// Move the ghost along and draw it, dimmed and behind everything that is really on the
+    /// board. It is only drawn while it is on the same level as the player.
+    pub(super) fn draw_ghost(&mut self) {
+        let Some(mut ghost) = self.ghost.take() else {
+            return;
+        };
+        ghost.catch_up(Instant::now());
+
+        let free = |game: &Self, x: u16, y: u16| {
+            game.occupancy[game.index(x, y)] == 0
+                && !game.is_wall(x, y)
+                && (x, y) != (game.food.x, game.food.y)
+        };
+
+        for (x, y) in ghost.drawn.drain(..) {
+            if free(self, x, y) {
+                self.draw_tile(x, y);
+            }
+        }
+
+        if !ghost.done && ghost.game.level == self.level {
+            write!(self.stdout, "{}", color::Fg(color::AnsiValue(GHOST_COLOR))).unwrap();
+            let body = &ghost.game.snakes[0].body;
+            for (k, part) in body.iter().enumerate() {
+                if !free(self, part.x, part.y) {
+                    continue;
+                }
+                let glyph = match body.get(k + 1) {
+                    None => head_glyph(part.direction),
+                    Some(_) if k == 0 => SNAKE_TAIL,
+                    Some(next) => body_glyph(part.direction.opposite(), next.direction),
+                };
+                write!(
+                    self.stdout,
+                    "{}{}",
+                    cursor::Goto(part.x + 1, part.y + 1),
+                    glyph
+                )
+                .unwrap();
+                ghost.drawn.push((part.x, part.y));
+            }
+            write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
+        }
+
+        self.ghost = Some(ghost);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let run = Run {
+            settings: "80x23 solid linear normal 30 normal=12 Open/Pillars".to_string(),
+            seed: u64::MAX,
+            score: -2,
+            turns: vec![
+                (0, Direction::Up),
+                (12, Direction::Left),
+                (345, Direction::Down),
+            ],
+        };
+        let line = run.encode();
+        assert!(!line.contains('\n'), "{line}");
+
+        let read = Run::parse(&line).unwrap();
+        assert_eq!(read.settings, run.settings);
+        assert_eq!((read.seed, read.score), (run.seed, run.score));
+        assert!(read.turns == run.turns);
+        assert_eq!(read.encode(), line);
+    }
+
+    #[test]
+    fn a_run_without_turns() {
+        let run = Run::parse("x\t1\t0\t").unwrap();
+        assert!(run.turns.is_empty());
+    }
+
+    #[test]
+    fn rejects_broken_lines() {
+        let err = Run::parse("x\t1\t0").err().unwrap();
+        assert!(err.contains("four fields"), "{err}");
+        let err = Run::parse("x\tseed\t0\t").err().unwrap();
+        assert!(err.contains("invalid seed"), "{err}");
+        let err = Run::parse("x\t1\tlots\t").err().unwrap();
+        assert!(err.contains("invalid score"), "{err}");
+        let err = Run::parse("x\t1\t0\t12X").err().unwrap();
+        assert!(err.contains("invalid direction"), "{err}");
+        let err = Run::parse("x\t1\t0\tU").err().unwrap();
+        assert!(err.contains("invalid move"), "{err}");
+        // A turn ending in a character of more than one byte is turned down, not split inside
+        // it.
+        let err = Run::parse("x\t1\t0\t12é").err().unwrap();
+        assert!(err.contains("invalid direction"), "{err}");
+    }
+}
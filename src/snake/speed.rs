@@ -0,0 +1,139 @@
+//! How fast the snakes move and how that changes over a game.
+//!
+//! Speeds are in moves per second.
+
+/// How the speed changes as the game goes on.
+#[derive(PartialEq, Clone, Copy, Default)]
+pub enum Curve {
+    /// The speed never changes.
+    Constant,
+    /// Every bite of food speeds the game up a little.
+    #[default]
+    Linear,
+    /// Every level is a bit faster than the one before it, but food does not matter.
+    Stepped,
+}
+
+impl Curve {
+    pub fn next(self) -> Curve {
+        match self {
+            Curve::Constant => Curve::Linear,
+            Curve::Linear => Curve::Stepped,
+            Curve::Stepped => Curve::Constant,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Curve::Constant => "constant",
+            Curve::Linear => "linear",
+            Curve::Stepped => "stepped",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Curve> {
+        [Curve::Constant, Curve::Linear, Curve::Stepped]
+            .into_iter()
+            .find(|curve| curve.name() == name)
+    }
+}
+
+/// Presets for the starting speed, how quickly it rises and how fast it can get.
+#[derive(PartialEq, Clone, Copy, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Difficulty> {
+        [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard]
+            .into_iter()
+            .find(|difficulty| difficulty.name() == name)
+    }
+
+    fn start(self) -> u64 {
+        match self {
+            Difficulty::Easy => 6,
+            Difficulty::Normal => 10,
+            Difficulty::Hard => 15,
+        }
+    }
+
+    /// How much a bite of food adds on the linear curve.
+    fn step(self) -> u64 {
+        match self {
+            Difficulty::Easy => 2,
+            Difficulty::Normal => 3,
+            Difficulty::Hard => 4,
+        }
+    }
+
+    fn max(self) -> u64 {
+        match self {
+            Difficulty::Easy => 20,
+            Difficulty::Normal => 30,
+            Difficulty::Hard => 50,
+        }
+    }
+}
+
+/// The speed settings of a game.
+#[derive(Clone, Copy, Default)]
+pub struct Pace {
+    pub curve: Curve,
+    pub difficulty: Difficulty,
+    /// Overrides the difficulty's top speed.
+    pub max: Option<u64>,
+}
+
+impl Pace {
+    /// The top speed.
+    pub fn max(&self) -> u64 {
+        self.max.unwrap_or(self.difficulty.max()).max(1)
+    }
+
+    /// The speed a level starts at, counting levels from 0. On the stepped curve each level
+    /// is as much faster as three bites of food are on the linear one.
+    pub fn start(&self, level: usize) -> u64 {
+        let speed = match self.curve {
+            Curve::Constant | Curve::Linear => self.difficulty.start(),
+            Curve::Stepped => self.difficulty.start() + level as u64 * self.difficulty.step() * 3,
+        };
+        speed.min(self.max())
+    }
+
+    /// The speed after a player's snake ate food that speeds the game up.
+    pub fn faster(&self, speed: u64) -> u64 {
+        match self.curve {
+            Curve::Linear => (speed + self.difficulty.step()).min(self.max()),
+            Curve::Constant | Curve::Stepped => speed,
+        }
+    }
+
+    /// The speed after eating slow-down food, which takes off three bites' worth but never
+    /// goes below the level's start.
+    pub fn slower(&self, speed: u64, level: usize) -> u64 {
+        speed
+            .saturating_sub(self.difficulty.step() * 3)
+            .max(self.start(level))
+    }
+}